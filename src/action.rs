@@ -0,0 +1,307 @@
+//! Actions of the programs on the game field
+//!
+//! Input and AI do not change the programs directly but queue actions, which the `ActionSystem`
+//! validates and applies one after the other. The rules of the actions are implemented once, on
+//! a `Battlefield`: the `ActionSystem` applies them to the world, the AI to its `Board`.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use amethyst::{
+    core::Time,
+    ecs::{Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::body::{ProgramBody, Side};
+use crate::command::{self, CommandError, CommandOutcome, CommandReport};
+use crate::grid::GameField;
+use crate::history::TurnHistory;
+use crate::pickup::Pickup;
+use crate::replay::Replay;
+use crate::turn::{Phase, TurnState};
+use crate::Walkable;
+
+/// Seconds between two applied actions, so moves are visible step by step
+const ACTION_DELAY: f32 = 0.15;
+
+/// An action of the program whose head is at `from`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    /// Moves the head onto an adjacent tile
+    Move { from: (u32, u32), to: (u32, u32) },
+    /// Executes the command with the given index on the tile at `target`
+    Command { from: (u32, u32), command: usize, target: (u32, u32) },
+}
+
+/// Why an action could not be applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionError {
+    /// No program of the acting side has its head at the start of the action
+    NoActor,
+    /// The tile is not next to the head, not walkable or occupied, or the program has no moves left
+    Blocked,
+    /// The command could not be executed
+    Command(CommandError),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionError::NoActor => write!(f, "no program of the acting side there"),
+            ActionError::Blocked => write!(f, "the program can not move there"),
+            ActionError::Command(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// The tiles and programs of a battle, as far as the rules of the actions need them
+pub trait Battlefield {
+    fn program(&self, entity: Entity) -> Option<&ProgramBody>;
+
+    fn program_mut(&mut self, entity: Entity) -> Option<&mut ProgramBody>;
+
+    /// The program occupying the grid position
+    fn occupant(&self, grid_position: (u32, u32)) -> Option<Entity>;
+
+    /// The program of `side` with its head at the grid position
+    fn actor(&self, head: (u32, u32), side: Side) -> Option<Entity>;
+
+    /// Removes a program which lost all its sectors
+    fn delete_program(&mut self, entity: Entity);
+
+    /// Whether the tile at the grid position is walkable, `None` outside of the field
+    fn is_walkable(&self, grid_position: (u32, u32)) -> Option<bool>;
+
+    fn set_walkable(&mut self, grid_position: (u32, u32), walkable: bool);
+
+    /// Whether a pickup lies on the tile at the grid position
+    fn has_pickup(&self, grid_position: (u32, u32)) -> bool;
+
+    /// Whether the program `entity` may move its head onto the grid position: a walkable tile which
+    /// no other program occupies
+    fn can_enter(&self, grid_position: (u32, u32), entity: Entity) -> bool {
+        self.is_walkable(grid_position) == Some(true)
+            && self.occupant(grid_position).map_or(true, |occupant| occupant == entity)
+    }
+}
+
+/// Applies the action of a program of `side`. Returns what a command did, `None` for a move.
+pub fn apply<B: Battlefield + ?Sized>(field: &mut B, action: Action, side: Side) -> Result<Option<CommandOutcome>, ActionError> {
+    let from = match action {
+        Action::Move { from, .. } | Action::Command { from, .. } => from,
+    };
+    let actor = field.actor(from, side).ok_or(ActionError::NoActor)?;
+
+    match action {
+        Action::Move { to, .. } => {
+            let enterable = field.can_enter(to, actor);
+            let body = field.program_mut(actor).ok_or(ActionError::NoActor)?;
+            if !body.can_step(to, enterable) {
+                return Err(ActionError::Blocked);
+            }
+            body.step(to);
+            Ok(None)
+        }
+        Action::Command { command, target, .. } => command::execute(field, actor, command, target)
+            .map(Some)
+            .map_err(ActionError::Command),
+    }
+}
+
+/// The programs and tiles of the world, for applying actions in the `ActionSystem`
+pub struct WorldField<'s, 'a> {
+    pub entities: &'s Entities<'a>,
+    pub game_field: &'s GameField,
+    pub walkable: &'s mut WriteStorage<'a, Walkable>,
+    pub bodies: &'s mut WriteStorage<'a, ProgramBody>,
+    pub pickups: &'s ReadStorage<'a, Pickup>,
+}
+
+impl<'s, 'a> Battlefield for WorldField<'s, 'a> {
+    fn program(&self, entity: Entity) -> Option<&ProgramBody> {
+        self.bodies.get(entity)
+    }
+
+    fn program_mut(&mut self, entity: Entity) -> Option<&mut ProgramBody> {
+        self.bodies.get_mut(entity)
+    }
+
+    fn occupant(&self, grid_position: (u32, u32)) -> Option<Entity> {
+        (self.entities, &*self.bodies)
+            .join()
+            .find(|(_, body)| body.occupies(grid_position))
+            .map(|(entity, _)| entity)
+    }
+
+    fn actor(&self, head: (u32, u32), side: Side) -> Option<Entity> {
+        (self.entities, &*self.bodies)
+            .join()
+            .find(|(_, body)| body.head() == head && body.side == side)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Deletes the program entity, its sprites are removed by the `BodySpriteSystem`
+    fn delete_program(&mut self, entity: Entity) {
+        self.bodies.remove(entity);
+        self.entities.delete(entity).expect("Failed to delete program.");
+    }
+
+    fn is_walkable(&self, grid_position: (u32, u32)) -> Option<bool> {
+        self.game_field
+            .tile(grid_position)
+            .and_then(|tile| self.walkable.get(tile))
+            .map(|tile| tile.walkable)
+    }
+
+    /// Only changes the `Walkable` of the tile, the `TileSpriteSystem` updates its sprites
+    fn set_walkable(&mut self, grid_position: (u32, u32), walkable: bool) {
+        if let Some(tile) = self.game_field.tile(grid_position).and_then(|tile| self.walkable.get_mut(tile)) {
+            tile.walkable = walkable;
+        }
+    }
+
+    fn has_pickup(&self, grid_position: (u32, u32)) -> bool {
+        self.game_field.tile(grid_position).map_or(false, |tile| self.pickups.contains(tile))
+    }
+}
+
+/// Resource of the actions waiting to be applied
+#[derive(Debug, Default)]
+pub struct ActionQueue {
+    actions: VecDeque<Action>,
+}
+
+impl ActionQueue {
+
+    pub fn push(&mut self, action: Action) {
+        self.actions.push_back(action);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.actions.clear();
+    }
+
+    fn pop(&mut self) -> Option<Action> {
+        self.actions.pop_front()
+    }
+}
+
+/// Returns the grid positions occupied by programs other than `except`
+pub fn occupied_cells<'b, I>(bodies: I, except: Option<Entity>) -> HashSet<(u32, u32)>
+    where I: Iterator<Item = (Entity, &'b ProgramBody)>
+{
+    bodies
+        .filter(|(entity, _)| Some(*entity) != except)
+        .flat_map(|(_, body)| body.sectors().iter().copied())
+        .collect()
+}
+
+/// Applies the queued actions of the side whose turn it is
+#[derive(Default)]
+pub struct ActionSystem {
+    cooldown: f32,
+}
+
+impl<'a> System<'a> for ActionSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, TurnState>,
+        Read<'a, GameField>,
+        Write<'a, ActionQueue>,
+        Write<'a, CommandReport>,
+        Write<'a, TurnHistory>,
+        Write<'a, Replay>,
+        WriteStorage<'a, Walkable>,
+        WriteStorage<'a, ProgramBody>,
+        ReadStorage<'a, Pickup>,
+    );
+
+    fn run(&mut self, ( entities,
+                        time,
+                        turn_state,
+                        game_field,
+                        mut action_queue,
+                        mut command_report,
+                        mut turn_history,
+                        mut replay,
+                        mut walkable,
+                        mut bodies,
+                        pickups,
+                        ): Self::SystemData) {
+
+        let active_side = match turn_state.phase {
+            Phase::PlayerTurn => Side::Player,
+            Phase::EnemyTurn => Side::Enemy,
+            _ => {
+                action_queue.clear();
+                return;
+            }
+        };
+
+        // Without a window nobody watches the moves step by step
+        self.cooldown -= time.delta_seconds();
+        if self.cooldown > 0.0 && !turn_state.simulated {
+            return;
+        }
+        let action = match action_queue.pop() {
+            Some(action) => action,
+            None => return,
+        };
+        self.cooldown = ACTION_DELAY;
+
+        let from = match action {
+            Action::Move { from, .. } | Action::Command { from, .. } => from,
+        };
+        let mut field = WorldField {
+            entities: &entities,
+            game_field: &game_field,
+            walkable: &mut walkable,
+            bodies: &mut bodies,
+            pickups: &pickups,
+        };
+        let can_undo = match action {
+            Action::Command { command, .. } => field
+                .actor(from, active_side)
+                .and_then(|actor| field.program(actor))
+                .and_then(|body| body.program.commands.get(command))
+                .map_or(true, |command| command.effect.can_undo()),
+            Action::Move { .. } => true,
+        };
+
+        match apply(&mut field, action, active_side) {
+            Ok(outcome) => {
+                if let Some(outcome) = outcome {
+                    info!("{}", outcome);
+                    command_report.last = Some(Ok(outcome));
+                }
+                if !can_undo {
+                    turn_history.lock();
+                }
+                if !turn_state.replaying {
+                    replay.record(action);
+                }
+            }
+            Err(ActionError::Command(error)) => {
+                warn!("{:?} failed: {}", action, error);
+                if active_side == Side::Player && !turn_state.replaying {
+                    turn_history.discard();
+                }
+                command_report.last = Some(Err(error));
+            }
+            Err(error) => {
+                warn!("Invalid action {:?} of side {:?}: {}", action, active_side, error);
+                // the rest of the path starts from where the program should have been
+                action_queue.clear();
+            }
+        }
+    }
+}
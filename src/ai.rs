@@ -0,0 +1,607 @@
+//! Control of the enemy programs, and of the player programs in a simulated battle
+//!
+//! The AI plans on a `Board` snapshot of the battle. On the easy difficulty each program moves
+//! towards the nearest program of the other side and attacks it with its strongest damaging
+//! command once one is in range. The other difficulties search the possible turns of the
+//! programs with alpha-beta pruning, limited in depth and by a `SearchBudget`.
+//!
+//! Against the player the budget is time, simulated battles limit the number of searched boards
+//! instead, so they play the same on every machine. The budget holds for the turn of a whole
+//! side, the `TurnPlanner` shares it between the programs still to act. During a battle the search
+//! runs on its own thread, so the game keeps drawing and handling input while the enemy thinks.
+
+use std::cell::Cell;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use amethyst::ecs::Entity;
+
+use log::warn;
+
+use crate::action::{Action, Battlefield};
+use crate::board::Board;
+use crate::body::{ProgramBody, Side};
+use crate::command::validate;
+use crate::grid::{distance, path_to, reachable, within_range};
+use crate::program::CommandEffect;
+
+/// Score of a board on which one side has no programs left
+const WIN_SCORE: i32 = 100_000;
+
+/// Resource choosing how well the enemy programs play
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Enemy programs follow simple rules
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Normal
+    }
+}
+
+impl Difficulty {
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    /// Number of program turns searched ahead, 0 for the rule-based AI
+    fn search_depth(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 4,
+        }
+    }
+
+    /// Time the search may take to plan the turn of all programs of a side
+    fn time_budget(&self) -> Duration {
+        match self {
+            Difficulty::Easy => Duration::from_millis(0),
+            Difficulty::Normal => Duration::from_millis(500),
+            Difficulty::Hard => Duration::from_millis(2000),
+        }
+    }
+
+    /// Number of boards the search may look at to plan the turn of all programs of a side
+    fn node_budget(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 5_000,
+            Difficulty::Hard => 20_000,
+        }
+    }
+
+    /// Number of turns searched for each program turn further down the search
+    fn branching(&self) -> usize {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 6,
+            Difficulty::Hard => 10,
+        }
+    }
+}
+
+/// What limits the search for the turn of a side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchBudget {
+    /// Time on the clock, so the enemy does not keep the player waiting
+    Time(Duration),
+    /// Number of searched boards, so a simulated battle plays the same on every machine
+    Nodes(u32),
+}
+
+impl SearchBudget {
+
+    /// The share of the budget for the next of `programs_left` programs
+    fn share(&self, programs_left: usize) -> Self {
+        let programs_left = programs_left.max(1) as u32;
+        match self {
+            SearchBudget::Time(time) => SearchBudget::Time(*time / programs_left),
+            SearchBudget::Nodes(nodes) => SearchBudget::Nodes(*nodes / programs_left),
+        }
+    }
+
+    /// The budget left after a search which took `time` and searched `nodes` boards
+    fn spend(&self, time: Duration, nodes: u32) -> Self {
+        match self {
+            SearchBudget::Time(left) => SearchBudget::Time(left.saturating_sub(time)),
+            SearchBudget::Nodes(left) => SearchBudget::Nodes(left.saturating_sub(nodes)),
+        }
+    }
+}
+
+/// Plans the turns of the programs of one side, one program after the other
+pub struct TurnPlanner {
+    difficulty: Difficulty,
+    /// Search budget left for the programs which did not act yet
+    budget: SearchBudget,
+    /// The program planned in the background, with the time its search started
+    pending: Option<(Entity, Instant, Receiver<(Vec<Action>, u32)>)>,
+}
+
+impl TurnPlanner {
+
+    /// A planner searching within the time budget of the difficulty
+    pub fn new(difficulty: Difficulty) -> Self {
+        TurnPlanner {
+            difficulty,
+            budget: SearchBudget::Time(difficulty.time_budget()),
+            pending: None,
+        }
+    }
+
+    /// A planner searching a fixed number of boards, so it plans the same turns on every machine
+    pub fn deterministic(difficulty: Difficulty) -> Self {
+        TurnPlanner {
+            difficulty,
+            budget: SearchBudget::Nodes(difficulty.node_budget()),
+            pending: None,
+        }
+    }
+
+    /// The share of the remaining budget for the next of `programs_left` programs
+    fn share(&self, programs_left: usize) -> SearchBudget {
+        self.budget.share(programs_left)
+    }
+
+    /// Plans the actions of the program `actor` for the rest of its turn, `programs_left` counting
+    /// it and the programs of its side acting after it.
+    ///
+    /// Returns no actions if the program should pass.
+    pub fn plan(&mut self, board: &Board, actor: Entity, programs_left: usize) -> Vec<Action> {
+        let started = Instant::now();
+        let (actions, nodes) = plan(board, actor, self.difficulty, self.share(programs_left));
+        self.budget = self.budget.spend(started.elapsed(), nodes);
+        actions
+    }
+
+    /// Starts planning the turn of `actor` on another thread, `poll` returns the plan
+    pub fn start(&mut self, board: Board, actor: Entity, programs_left: usize) {
+        let (sender, receiver) = mpsc::channel();
+        let (difficulty, budget) = (self.difficulty, self.share(programs_left));
+        thread::spawn(move || {
+            // The receiver is gone if the battle ended in the meantime
+            let _ = sender.send(plan(&board, actor, difficulty, budget));
+        });
+        self.pending = Some((actor, Instant::now(), receiver));
+    }
+
+    pub fn is_planning(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the program with its planned actions once the search started by `start` finished
+    pub fn poll(&mut self) -> Option<(Entity, Vec<Action>)> {
+        let (actions, nodes) = match &self.pending {
+            Some((_, _, receiver)) => match receiver.try_recv() {
+                Ok(planned) => planned,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {
+                    warn!("Planning the turn of a program failed, it passes");
+                    (Vec::new(), 0)
+                }
+            },
+            None => return None,
+        };
+        let (actor, started, _) = self.pending.take().unwrap();
+        self.budget = self.budget.spend(started.elapsed(), nodes);
+        Some((actor, actions))
+    }
+}
+
+/// Plans the turn of the program `actor` of either side within the `budget`, returns the actions
+/// with the number of searched boards
+fn plan(board: &Board, actor: Entity, difficulty: Difficulty, budget: SearchBudget) -> (Vec<Action>, u32) {
+    if difficulty.search_depth() == 0 {
+        (rule_based_plan(board, actor), 0)
+    } else {
+        search_plan(board, actor, difficulty, budget)
+    }
+}
+
+fn opponent(side: Side) -> Side {
+    match side {
+        Side::Player => Side::Enemy,
+        Side::Enemy => Side::Player,
+    }
+}
+
+/// Sectors of all programs of the given side
+fn sectors_of(board: &Board, side: Side) -> Vec<(u32, u32)> {
+    board.programs()
+        .iter()
+        .filter(|(_, body)| body.side == side)
+        .flat_map(|(_, body)| body.sectors().iter().copied())
+        .collect()
+}
+
+/// Returns the index of the most damaging command of the program which can hit one of the
+/// `targets` from `head`, together with the target to hit
+fn best_attack(body: &ProgramBody, head: (u32, u32), targets: &[(u32, u32)]) -> Option<(usize, (u32, u32))> {
+    body.program.commands
+        .iter()
+        .enumerate()
+        .filter_map(|(index, command)| match command.effect {
+            CommandEffect::Damage(damage) => Some((index, command.range, damage)),
+            _ => None,
+        })
+        .filter_map(|(index, range, damage)| {
+            targets
+                .iter()
+                .filter(|target| distance(head, **target) <= range)
+                .min_by_key(|target| distance(head, **target))
+                .map(|target| (damage, index, *target))
+        })
+        .max_by_key(|(damage, index, _)| (*damage, std::cmp::Reverse(*index)))
+        .map(|(_, index, target)| (index, target))
+}
+
+/// Returns the moves to every tile the program can reach this turn, sorted by destination
+fn paths(board: &Board, actor: Entity) -> Vec<((u32, u32), Vec<Action>)> {
+    let body = match board.program(actor) {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+    let reached = reachable(body.head(), body.moves_left(), board.extent(), |grid_position| {
+        board.can_enter(grid_position, actor)
+    });
+
+    let mut destinations: Vec<(u32, u32)> = reached.keys().copied().collect();
+    destinations.sort();
+    destinations
+        .into_iter()
+        .filter_map(|destination| path_to(&reached, destination).map(|path| (destination, path)))
+        .map(|(destination, path)| {
+            let moves = path
+                .windows(2)
+                .map(|step| Action::Move { from: step[0], to: step[1] })
+                .collect();
+            (destination, moves)
+        })
+        .collect()
+}
+
+/// Moves towards the nearest program of the other side and attacks it once in range
+fn rule_based_plan(board: &Board, actor: Entity) -> Vec<Action> {
+    let body = match board.program(actor) {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+    let targets = sectors_of(board, opponent(body.side));
+    if targets.is_empty() {
+        return Vec::new();
+    }
+
+    // Prefer tiles to attack from, then tiles closer to a program of the other side, then
+    // shorter paths
+    let nearest_target = |grid_position: (u32, u32)| {
+        targets.iter().map(|target| distance(grid_position, *target)).min().unwrap_or(u32::MAX)
+    };
+    let destination = paths(board, actor)
+        .into_iter()
+        .min_by_key(|(destination, moves)| {
+            let can_attack = best_attack(body, *destination, &targets).is_some();
+            (!can_attack, nearest_target(*destination), moves.len())
+        });
+
+    let mut actions = Vec::new();
+    if let Some((destination, moves)) = destination {
+        actions.extend(moves);
+        if let Some((command, target)) = best_attack(body, destination, &targets) {
+            actions.push(Action::Command { from: destination, command, target });
+        }
+    }
+    actions
+}
+
+/// Applies the actions of a program of `side` to a copy of the board, `None` if one of them is
+/// invalid
+fn play(board: &Board, side: Side, actions: &[Action]) -> Option<Board> {
+    let mut board = board.clone();
+    for action in actions {
+        if !board.apply(*action, side) {
+            return None;
+        }
+    }
+    Some(board)
+}
+
+/// All turns the program can take: moving to any reachable tile, optionally followed by any of
+/// its commands on a valid target. Returns the actions of each turn with the resulting board.
+fn turns(board: &Board, actor: Entity) -> Vec<(Vec<Action>, Board)> {
+    let side = match board.program(actor) {
+        Some(body) => body.side,
+        None => return Vec::new(),
+    };
+
+    let mut turns = Vec::new();
+    for (destination, moves) in paths(board, actor) {
+        let moved = match play(board, side, &moves) {
+            Some(moved) => moved,
+            None => continue,
+        };
+        if let Some(body) = moved.program(actor) {
+            for (command, details) in body.program.commands.iter().enumerate() {
+                for target in within_range(destination, details.range, moved.extent()) {
+                    let target_body = moved.occupant(target).and_then(|entity| moved.program(entity));
+                    if validate(body, command, target, target_body, moved.is_walkable(target), moved.has_pickup(target)).is_err() {
+                        continue;
+                    }
+                    let action = Action::Command { from: destination, command, target };
+                    if let Some(commanded) = play(&moved, side, &[action]) {
+                        let mut actions = moves.clone();
+                        actions.push(action);
+                        turns.push((actions, commanded));
+                    }
+                }
+            }
+        }
+        turns.push((moves, moved));
+    }
+    turns
+}
+
+/// Scores the board from the view of `side`: more programs and sectors are better, and programs
+/// of `side` close to their targets
+fn evaluate(board: &Board, side: Side) -> i32 {
+    if !board.has_programs(opponent(side)) {
+        return WIN_SCORE;
+    }
+    if !board.has_programs(side) {
+        return -WIN_SCORE;
+    }
+
+    let mut score = 0;
+    for (_, body) in board.programs() {
+        let sign = if body.side == side { 1 } else { -1 };
+        score += sign * (50 + 10 * body.size() as i32);
+    }
+
+    let target_sectors = sectors_of(board, opponent(side));
+    for (_, body) in board.programs().iter().filter(|(_, body)| body.side == side) {
+        let nearest = target_sectors
+            .iter()
+            .map(|sector| distance(body.head(), *sector))
+            .min()
+            .unwrap_or(0);
+        score -= nearest as i32;
+    }
+    score
+}
+
+/// Alpha-beta search over the turns of single programs, limited in depth and by a budget.
+///
+/// The sides take turns with one program each, starting with the other side after the program
+/// whose turn is planned. The side of that program maximizes the score, the other side minimizes
+/// it.
+struct Search {
+    /// The side of the program whose turn is planned
+    planner: Side,
+    budget: SearchBudget,
+    started: Instant,
+    /// Number of boards searched so far
+    nodes: Cell<u32>,
+    branching: usize,
+}
+
+impl Search {
+
+    fn is_exhausted(&self) -> bool {
+        match self.budget {
+            SearchBudget::Time(time) => self.started.elapsed() > time,
+            SearchBudget::Nodes(nodes) => self.nodes.get() > nodes,
+        }
+    }
+
+    /// The boards after the most promising turns of any program of `side`
+    fn children(&self, board: &Board, side: Side) -> Vec<Board> {
+        let mut children: Vec<(i32, Board)> = board.programs()
+            .iter()
+            .filter(|(_, body)| body.side == side)
+            .map(|(entity, _)| *entity)
+            .flat_map(|entity| {
+                let mut started = board.clone();
+                if let Some(body) = started.program_mut(entity) {
+                    body.begin_turn();
+                }
+                turns(&started, entity)
+            })
+            .map(|(_, child)| (evaluate(&child, self.planner), child))
+            .collect();
+
+        if side == self.planner {
+            children.sort_by_key(|(score, _)| -*score);
+        } else {
+            children.sort_by_key(|(score, _)| *score);
+        }
+        children.truncate(self.branching);
+        children.into_iter().map(|(_, child)| child).collect()
+    }
+
+    /// Returns the score of the board with `side` to play, `None` if the budget ran out
+    fn alpha_beta(&self, board: &Board, depth: u32, side: Side, mut alpha: i32, mut beta: i32) -> Option<i32> {
+        self.nodes.set(self.nodes.get() + 1);
+        if self.is_exhausted() {
+            return None;
+        }
+        let score = evaluate(board, self.planner);
+        if depth == 0 || score.abs() == WIN_SCORE {
+            return Some(score);
+        }
+        let children = self.children(board, side);
+        if children.is_empty() {
+            return Some(score);
+        }
+
+        let maximizing = side == self.planner;
+        let mut best = if maximizing { i32::MIN } else { i32::MAX };
+        for child in children {
+            let value = self.alpha_beta(&child, depth - 1, opponent(side), alpha, beta)?;
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(best);
+            } else {
+                best = best.min(value);
+                beta = beta.min(best);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+/// Searches the best turn of the program `actor`, deepening the search until the depth limit of
+/// the difficulty is reached or the `budget` is used up. Returns the actions with the number of
+/// searched boards.
+fn search_plan(board: &Board, actor: Entity, difficulty: Difficulty, budget: SearchBudget) -> (Vec<Action>, u32) {
+    let planner = match board.program(actor) {
+        Some(body) => body.side,
+        None => return (Vec::new(), 0),
+    };
+    let search = Search {
+        planner,
+        budget,
+        started: Instant::now(),
+        nodes: Cell::new(0),
+        branching: difficulty.branching(),
+    };
+
+    let mut turns = turns(board, actor);
+    turns.sort_by_key(|(_, child)| -evaluate(child, planner));
+
+    // Until a search depth is completed, the rule-based turn is the best guess
+    let mut best_plan = rule_based_plan(board, actor);
+    for depth in 1..=difficulty.search_depth() {
+        let mut best: Option<(i32, &Vec<Action>)> = None;
+        let mut alpha = i32::MIN;
+        for (actions, child) in &turns {
+            let value = match search.alpha_beta(child, depth - 1, opponent(planner), alpha, i32::MAX) {
+                Some(value) => value,
+                None => return (best_plan, search.nodes.get()),
+            };
+            if best.map_or(true, |(best_value, _)| value > best_value) {
+                best = Some((value, actions));
+                alpha = value;
+            }
+        }
+        if let Some((_, actions)) = best {
+            best_plan = actions.clone();
+        }
+    }
+    (best_plan, search.nodes.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::{Builder, World, WorldExt};
+
+    use super::*;
+    use crate::grid::GameField;
+    use crate::pickup::Pickup;
+    use crate::program::{test_program, Program};
+    use crate::Walkable;
+
+    /// Enough boards for every search on the small test boards to reach its full depth
+    const BUDGET: SearchBudget = SearchBudget::Nodes(1_000_000);
+
+    fn program(speed: u32, commands: &str) -> Program {
+        test_program(speed, 4, commands)
+    }
+
+    /// A board without voids at the start of a turn of all the programs, given in order
+    fn board(extent: (u32, u32), bodies: Vec<ProgramBody>) -> (Board, Vec<Entity>) {
+        let mut world = World::new();
+        world.register::<Walkable>();
+        world.register::<ProgramBody>();
+        world.register::<Pickup>();
+        let tiles = (0..extent.0 * extent.1)
+            .map(|_| world.create_entity().with(Walkable::new(true)).build())
+            .collect();
+        world.insert(GameField::new(extent, tiles));
+        let programs = bodies
+            .into_iter()
+            .map(|mut body| {
+                body.begin_turn();
+                world.create_entity().with(body).build()
+            })
+            .collect();
+        (Board::from_world(&world), programs)
+    }
+
+    #[test]
+    fn evaluate_wins_once_the_other_side_is_deleted() {
+        let hack = program(2, "(name: \"Slice\", range: 1, effect: Damage(2))");
+        let (board, _) = board((3, 3), vec![ProgramBody::new(hack, Side::Enemy, (1, 1))]);
+        assert_eq!(evaluate(&board, Side::Enemy), WIN_SCORE);
+        assert_eq!(evaluate(&board, Side::Player), -WIN_SCORE);
+    }
+
+    #[test]
+    fn search_takes_the_kill_in_range() {
+        let hack = program(2, "(name: \"Slice\", range: 1, effect: Damage(2))");
+        let (board, programs) = board((5, 5), vec![
+            ProgramBody::new(hack.clone(), Side::Enemy, (1, 1)),
+            ProgramBody::new(hack, Side::Player, (2, 1)),
+        ]);
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].iter() {
+            let (actions, _) = plan(&board, programs[0], *difficulty, BUDGET);
+            let deleted = play(&board, Side::Enemy, &actions).unwrap();
+            assert!(!deleted.has_programs(Side::Player), "{:?} plans {:?}", difficulty, actions);
+        }
+    }
+
+    #[test]
+    fn finished_search_beats_the_rule_based_plan() {
+        // The rule-based plan attacks the nearest program, the search deletes the smaller one
+        let phaser = program(0, "(name: \"Phaser\", range: 2, effect: Damage(2))");
+        let target = program(0, "");
+        let (board, programs) = board((6, 6), vec![
+            ProgramBody::new(phaser, Side::Enemy, (2, 2)),
+            ProgramBody::restore(target.clone(), Side::Player, &[(3, 2), (4, 2), (5, 2)], 0, false),
+            ProgramBody::new(target, Side::Player, (2, 4)),
+        ]);
+        let nearest = Action::Command { from: (2, 2), command: 0, target: (3, 2) };
+        let smallest = Action::Command { from: (2, 2), command: 0, target: (2, 4) };
+        assert_eq!(rule_based_plan(&board, programs[0]), vec![nearest]);
+        assert_eq!(search_plan(&board, programs[0], Difficulty::Normal, BUDGET).0, vec![smallest]);
+        // Without a budget the search cannot improve on the rule-based plan
+        assert_eq!(search_plan(&board, programs[0], Difficulty::Normal, SearchBudget::Nodes(0)).0, vec![nearest]);
+    }
+
+    #[test]
+    fn planner_shares_the_budget_of_the_turn() {
+        let mut planner = TurnPlanner::new(Difficulty::Hard);
+        assert_eq!(planner.share(4), SearchBudget::Time(Difficulty::Hard.time_budget() / 4));
+        planner.budget = SearchBudget::Time(Duration::from_millis(0));
+        assert_eq!(planner.share(1), SearchBudget::Time(Duration::from_millis(0)));
+    }
+
+    #[test]
+    fn deterministic_planner_spends_searched_boards() {
+        let hack = program(2, "(name: \"Slice\", range: 1, effect: Damage(2))");
+        let (board, programs) = board((5, 5), vec![
+            ProgramBody::new(hack.clone(), Side::Enemy, (0, 0)),
+            ProgramBody::new(hack, Side::Player, (4, 4)),
+        ]);
+        let mut planner = TurnPlanner::deterministic(Difficulty::Normal);
+        let actions = planner.plan(&board, programs[0], 1);
+        assert_eq!(TurnPlanner::deterministic(Difficulty::Normal).plan(&board, programs[0], 1), actions);
+        match planner.budget {
+            SearchBudget::Nodes(left) => assert!(left < Difficulty::Normal.node_budget()),
+            SearchBudget::Time(_) => panic!("deterministic planner with a time budget"),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! Plays a level with the AI on both sides, without a window, see `spybotics_clone::simulate`
+
+fn main() -> amethyst::Result<()> {
+    spybotics_clone::simulate::main()
+}
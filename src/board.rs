@@ -0,0 +1,139 @@
+//! Snapshot of a battle, independent of the ECS
+//!
+//! The board is a `Battlefield`, actions are applied to it by the same rules as in the
+//! `ActionSystem`, so the AI can try out actions without touching the world.
+
+use std::collections::HashSet;
+
+use amethyst::ecs::{Entity, Join, World, WorldExt};
+
+use crate::action::{self, Action, Battlefield};
+use crate::body::{ProgramBody, Side};
+use crate::grid::GameField;
+use crate::pickup::Pickup;
+use crate::Walkable;
+
+/// The tiles and programs of a battle
+#[derive(Debug, Clone)]
+pub struct Board {
+    extent: (u32, u32),
+    /// Whether the tile at a grid position is walkable, indexed like the `GameField`
+    walkable: Vec<bool>,
+    /// The programs on the field with the entities they belong to
+    programs: Vec<(Entity, ProgramBody)>,
+    /// Grid positions of the pickups lying on the field when the snapshot was taken
+    pickups: HashSet<(u32, u32)>,
+}
+
+impl Board {
+
+    /// Takes a snapshot of the game field and the programs on it
+    pub fn from_world(world: &World) -> Self {
+        let game_field = world.read_resource::<GameField>();
+        let walkable_storage = world.read_storage::<Walkable>();
+        let pickup_storage = world.read_storage::<Pickup>();
+        let extent = game_field.extent();
+
+        let mut walkable = Vec::with_capacity((extent.0 * extent.1) as usize);
+        let mut pickups = HashSet::new();
+        for i in 0..extent.0 {
+            for j in 0..extent.1 {
+                let tile = game_field.tile((i, j));
+                walkable.push(tile
+                    .and_then(|tile| walkable_storage.get(tile))
+                    .map_or(false, |tile| tile.walkable));
+                if tile.map_or(false, |tile| pickup_storage.contains(tile)) {
+                    pickups.insert((i, j));
+                }
+            }
+        }
+
+        let entities = world.entities();
+        let bodies = world.read_storage::<ProgramBody>();
+        let programs = (&entities, &bodies)
+            .join()
+            .map(|(entity, body)| (entity, body.clone()))
+            .collect();
+
+        Board {
+            extent,
+            walkable,
+            programs,
+            pickups,
+        }
+    }
+
+    pub fn extent(&self) -> (u32, u32) {
+        self.extent
+    }
+
+    /// Index of the grid position in `walkable`, `None` outside of the field
+    fn index(&self, grid_position: (u32, u32)) -> Option<usize> {
+        if grid_position.0 >= self.extent.0 || grid_position.1 >= self.extent.1 {
+            return None;
+        }
+        Some((grid_position.0 * self.extent.1 + grid_position.1) as usize)
+    }
+
+    pub fn programs(&self) -> &[(Entity, ProgramBody)] {
+        &self.programs
+    }
+
+    /// Whether a program of the given side is left
+    pub fn has_programs(&self, side: Side) -> bool {
+        self.programs.iter().any(|(_, body)| body.side == side)
+    }
+
+    /// Applies the action of a program of `side`, returns whether the action was valid
+    pub fn apply(&mut self, action: Action, side: Side) -> bool {
+        action::apply(self, action, side).is_ok()
+    }
+}
+
+impl Battlefield for Board {
+    fn program(&self, entity: Entity) -> Option<&ProgramBody> {
+        self.programs
+            .iter()
+            .find(|(program, _)| *program == entity)
+            .map(|(_, body)| body)
+    }
+
+    fn program_mut(&mut self, entity: Entity) -> Option<&mut ProgramBody> {
+        self.programs
+            .iter_mut()
+            .find(|(program, _)| *program == entity)
+            .map(|(_, body)| body)
+    }
+
+    fn occupant(&self, grid_position: (u32, u32)) -> Option<Entity> {
+        self.programs
+            .iter()
+            .find(|(_, body)| body.occupies(grid_position))
+            .map(|(entity, _)| *entity)
+    }
+
+    fn actor(&self, head: (u32, u32), side: Side) -> Option<Entity> {
+        self.programs
+            .iter()
+            .find(|(_, body)| body.head() == head && body.side == side)
+            .map(|(entity, _)| *entity)
+    }
+
+    fn delete_program(&mut self, entity: Entity) {
+        self.programs.retain(|(program, _)| *program != entity);
+    }
+
+    fn is_walkable(&self, grid_position: (u32, u32)) -> Option<bool> {
+        self.index(grid_position).map(|index| self.walkable[index])
+    }
+
+    fn set_walkable(&mut self, grid_position: (u32, u32), walkable: bool) {
+        if let Some(index) = self.index(grid_position) {
+            self.walkable[index] = walkable;
+        }
+    }
+
+    fn has_pickup(&self, grid_position: (u32, u32)) -> bool {
+        self.pickups.contains(&grid_position)
+    }
+}
@@ -0,0 +1,326 @@
+//! Bodies of the programs on the game field
+//!
+//! A program is a snake: its head is followed by tail sectors which grow as the program moves,
+//! until the program reaches its maximal size and the oldest sectors are dropped.
+
+use std::collections::{HashMap, VecDeque};
+
+use amethyst::{
+    core::Transform,
+    ecs::{Builder, Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, World, WorldExt, WriteStorage},
+    renderer::SpriteRender,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{adjacent, tile_sprite_transform, GameField, PROGRAM_LAYER};
+use crate::program::{Program, ProgramCatalog};
+use crate::{GameTilePosition, GameTileSpriteStack, HandleHandle};
+
+/// The side a program fights for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Player,
+    Enemy,
+}
+
+/// Component of a program placed on the game field
+#[derive(Debug, Clone)]
+pub struct ProgramBody {
+    pub program: Program,
+    pub side: Side,
+    /// Grid positions of the occupied tiles, starting with the head
+    sectors: VecDeque<(u32, u32)>,
+    /// Tiles the program can still move this turn
+    moves_left: u32,
+    /// Whether the program has finished its turn
+    done: bool,
+}
+
+impl ProgramBody {
+
+    /// Creates a program consisting only of its head
+    pub fn new(program: Program, side: Side, head: (u32, u32)) -> Self {
+        let mut sectors = VecDeque::new();
+        sectors.push_back(head);
+        ProgramBody {
+            program,
+            side,
+            sectors,
+            moves_left: 0,
+            done: true,
+        }
+    }
+
+    /// Creates a program in the state it was saved in, `sectors` starting with the head
+    pub fn restore(program: Program, side: Side, sectors: &[(u32, u32)], moves_left: u32, done: bool) -> Self {
+        ProgramBody {
+            program,
+            side,
+            sectors: sectors.iter().copied().collect(),
+            moves_left,
+            done,
+        }
+    }
+
+    pub fn head(&self) -> (u32, u32) {
+        *self.sectors.front().expect("Program body without sectors.")
+    }
+
+    pub fn sectors(&self) -> &VecDeque<(u32, u32)> {
+        &self.sectors
+    }
+
+    pub fn size(&self) -> usize {
+        self.sectors.len()
+    }
+
+    pub fn occupies(&self, grid_position: (u32, u32)) -> bool {
+        self.sectors.contains(&grid_position)
+    }
+
+    pub fn moves_left(&self) -> u32 {
+        self.moves_left
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Restores the moves of the program at the start of the turn of its side
+    pub fn begin_turn(&mut self) {
+        self.moves_left = self.program.speed;
+        self.done = false;
+    }
+
+    pub fn end_turn(&mut self) {
+        self.moves_left = 0;
+        self.done = true;
+    }
+
+    /// Whether the program can move its head onto the grid position, `enterable` telling whether
+    /// the tile there is walkable and free of other programs
+    pub fn can_step(&self, grid_position: (u32, u32), enterable: bool) -> bool {
+        self.moves_left > 0 && adjacent(self.head(), grid_position) && enterable
+    }
+
+    /// Moves the program by one tile, using up one of its moves
+    pub fn step(&mut self, grid_position: (u32, u32)) {
+        self.move_head(grid_position);
+        self.moves_left = self.moves_left.saturating_sub(1);
+        if self.moves_left == 0 && self.program.commands.is_empty() {
+            self.done = true;
+        }
+    }
+
+    /// Removes up to `count` sectors from the end of the tail, returns the number of removed sectors
+    pub fn remove_sectors(&mut self, count: usize) -> usize {
+        let removed = count.min(self.sectors.len());
+        self.sectors.truncate(self.sectors.len() - removed);
+        removed
+    }
+
+    /// Moves the head onto the given grid position.
+    ///
+    /// The previous head becomes a tail sector. Moving onto an own sector does not grow the
+    /// program, and sectors beyond the maximal size are dropped from the end of the tail.
+    pub fn move_head(&mut self, grid_position: (u32, u32)) {
+        if let Some(index) = self.sectors.iter().position(|sector| *sector == grid_position) {
+            self.sectors.remove(index);
+        }
+        self.sectors.push_front(grid_position);
+        self.sectors.truncate(self.program.max_size.max(1));
+    }
+}
+
+impl Component for ProgramBody {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Places the named programs of the program catalog with their heads at the grid positions
+pub fn place_programs(world: &mut World, programs: &[(String, (u32, u32))], side: Side) {
+    world.register::<ProgramBody>();
+
+    let catalog = (*world.read_resource::<ProgramCatalog>()).clone();
+    for (name, grid_position) in programs.iter() {
+        match catalog.get(name) {
+            Some(program) => {
+                world
+                    .create_entity()
+                    .with(ProgramBody::new(program.clone(), side, *grid_position))
+                    .build();
+            }
+            None => warn!("Program {} is missing in the program catalog", name),
+        }
+    }
+}
+
+/// Sectors and sprites last drawn for a program
+struct DrawnBody {
+    sectors: Vec<(u32, u32)>,
+    sprites: Vec<((u32, u32), Entity)>,
+}
+
+/// Keeps the sprites of the program sectors in sync with the program bodies.
+///
+/// Whenever a body changes or its program is deleted, the old sector sprites are removed from the
+/// tile sprite stacks and new ones are pushed.
+#[derive(Default)]
+pub struct BodySpriteSystem {
+    drawn: HashMap<Entity, DrawnBody>,
+}
+
+impl<'a> System<'a> for BodySpriteSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, HandleHandle>,
+        Read<'a, GameField>,
+        ReadStorage<'a, ProgramBody>,
+        ReadStorage<'a, GameTilePosition>,
+        WriteStorage<'a, GameTileSpriteStack>,
+        WriteStorage<'a, SpriteRender>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, ( entities,
+                        sprite_sheet_handle,
+                        game_field,
+                        bodies,
+                        game_tile_position,
+                        mut game_tile_sprite_stack,
+                        mut sprites,
+                        mut transforms,
+                        ): Self::SystemData) {
+
+        let sprite_sheet = match sprite_sheet_handle.sprite_sheet_handle.as_ref() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        // Remove the sprites of programs which moved or got deleted
+        let outdated: Vec<Entity> = self.drawn
+            .iter()
+            .filter(|(entity, drawn)| match bodies.get(**entity) {
+                Some(body) => !body.sectors().iter().eq(drawn.sectors.iter()),
+                None => true,
+            })
+            .map(|(entity, _)| *entity)
+            .collect();
+
+        for entity in outdated {
+            let drawn = self.drawn.remove(&entity).unwrap();
+            for (grid_position, sprite) in drawn.sprites {
+                if let Some(tile_stack) = game_field.tile(grid_position)
+                    .and_then(|tile| game_tile_sprite_stack.get_mut(tile)) {
+                    tile_stack.sprite_stack.retain(|stacked| *stacked != sprite);
+                }
+                // Sprites of a finished battle are already deleted
+                if entities.is_alive(sprite) {
+                    entities.delete(sprite).expect("Failed to delete sector sprite.");
+                }
+            }
+        }
+
+        // Draw every program which is not drawn yet
+        for (entity, body) in (&entities, &bodies).join() {
+            if self.drawn.contains_key(&entity) {
+                continue;
+            }
+
+            let mut drawn = DrawnBody {
+                sectors: body.sectors().iter().copied().collect(),
+                sprites: Vec::new(),
+            };
+
+            for (index, grid_position) in body.sectors().iter().enumerate() {
+                let tile = match game_field.tile(*grid_position) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let sprite_transform = match game_tile_position.get(tile) {
+                    Some(tile_position) => tile_sprite_transform(tile_position, PROGRAM_LAYER),
+                    None => continue,
+                };
+
+                // Tail sectors point towards the previous sector, so the body looks connected
+                let sprite_number = match index {
+                    0 => body.program.head,
+                    _ => body.program.tail.towards(*grid_position, body.sectors()[index - 1]),
+                };
+                let sprite_render = SpriteRender {
+                    sprite_sheet: sprite_sheet.clone(),
+                    sprite_number,
+                };
+
+                let sprite = entities
+                    .build_entity()
+                    .with(sprite_render, &mut sprites)
+                    .with(sprite_transform, &mut transforms)
+                    .build();
+
+                if let Some(tile_stack) = game_tile_sprite_stack.get_mut(tile) {
+                    tile_stack.sprite_stack.push(sprite);
+                }
+                drawn.sprites.push((*grid_position, sprite));
+            }
+
+            self.drawn.insert(entity, drawn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program::test_program;
+
+    fn sectors(body: &ProgramBody) -> Vec<(u32, u32)> {
+        body.sectors().iter().copied().collect()
+    }
+
+    #[test]
+    fn moving_grows_the_tail_up_to_the_maximal_size() {
+        let mut body = ProgramBody::new(test_program(5, 3, ""), Side::Player, (0, 0));
+        body.move_head((0, 1));
+        assert_eq!(sectors(&body), vec![(0, 1), (0, 0)]);
+        body.move_head((0, 2));
+        assert_eq!(sectors(&body), vec![(0, 2), (0, 1), (0, 0)]);
+        body.move_head((1, 2));
+        assert_eq!(sectors(&body), vec![(1, 2), (0, 2), (0, 1)]);
+    }
+
+    #[test]
+    fn moving_onto_an_own_sector_does_not_grow() {
+        let mut body = ProgramBody::restore(test_program(5, 4, ""), Side::Player, &[(1, 1), (1, 0), (0, 0)], 5, false);
+        body.move_head((1, 0));
+        assert_eq!(sectors(&body), vec![(1, 0), (1, 1), (0, 0)]);
+        body.move_head((0, 0));
+        assert_eq!(sectors(&body), vec![(0, 0), (1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn steps_use_up_the_moves() {
+        let mut body = ProgramBody::new(test_program(2, 4, ""), Side::Player, (0, 0));
+        body.begin_turn();
+        assert!(body.can_step((0, 1), true));
+        assert!(!body.can_step((0, 1), false));
+        assert!(!body.can_step((1, 1), true));
+        body.step((0, 1));
+        body.step((0, 2));
+        assert_eq!(body.moves_left(), 0);
+        assert!(!body.can_step((0, 3), true));
+        // Without commands there is nothing left to do
+        assert!(body.is_done());
+    }
+
+    #[test]
+    fn damage_removes_sectors_from_the_end_of_the_tail() {
+        let mut body = ProgramBody::restore(test_program(1, 4, ""), Side::Enemy, &[(2, 2), (2, 1), (2, 0)], 0, true);
+        assert_eq!(body.remove_sectors(2), 2);
+        assert_eq!(sectors(&body), vec![(2, 2)]);
+        assert_eq!(body.remove_sectors(3), 1);
+        assert_eq!(body.size(), 0);
+    }
+}
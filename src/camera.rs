@@ -0,0 +1,201 @@
+//! Framing the game field with the camera
+//!
+//! The camera shows the whole board at first. The player can zoom in with the mouse wheel and pan
+//! by dragging with the middle mouse button or with the arrow keys and WASD, always staying inside
+//! the board.
+
+use amethyst::{
+    core::{Time, Transform},
+    ecs::{Entities, Join, Read, ReadExpect, System, WriteStorage},
+    input::{InputHandler, StringBindings},
+    renderer::{camera::ActiveCamera, Camera},
+    window::ScreenDimensions,
+    winit::{MouseButton, VirtualKeyCode},
+};
+
+use crate::grid::GameField;
+
+/// How far the camera can zoom in, relative to the view of the whole board
+const MAX_ZOOM: f32 = 4.0;
+/// Zoom factor of one step of the mouse wheel
+const ZOOM_STEP: f32 = 1.1;
+/// Screen pixels per second the camera pans when a key is held
+const PAN_SPEED: f32 = 400.0;
+
+/// Returns the size of the world area to show so that a board of `board_size` fits completely into
+/// a window of `screen_size`, without distorting the tiles
+pub fn fit_view(board_size: (f32, f32), screen_size: (f32, f32)) -> (f32, f32) {
+    let scale = (board_size.0 / screen_size.0).max(board_size.1 / screen_size.1);
+    (screen_size.0 * scale, screen_size.1 * scale)
+}
+
+/// Returns the position of the camera closest to `center` which shows only the board, or the
+/// middle of the board along an axis where the view is larger than the board
+fn clamp_center(center: (f32, f32), view_size: (f32, f32), board_size: (f32, f32)) -> (f32, f32) {
+    let clamp_axis = |center: f32, view: f32, board: f32| {
+        if view >= board {
+            board * 0.5
+        } else {
+            center.max(view * 0.5).min(board - view * 0.5)
+        }
+    };
+    (
+        clamp_axis(center.0, view_size.0, board_size.0),
+        clamp_axis(center.1, view_size.1, board_size.1),
+    )
+}
+
+/// What the camera currently shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct View {
+    screen_size: (f32, f32),
+    board_size: (f32, f32),
+    /// World position in the middle of the screen
+    center: (f32, f32),
+    /// 1 shows the whole board, larger values show less of it
+    zoom: f32,
+}
+
+/// Keeps the camera on the game field, handling zoom, panning and resizing of the window.
+///
+/// Loading another board resets the view to the whole board.
+#[derive(Default)]
+pub struct CameraSystem {
+    /// The view the camera was last set to
+    view: Option<View>,
+    /// Mouse position of the last frame while dragging with the middle mouse button
+    drag_from: Option<(f32, f32)>,
+}
+
+impl<'a> System<'a> for CameraSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, InputHandler<StringBindings>>,
+        Read<'a, ActiveCamera>,
+        Read<'a, GameField>,
+        ReadExpect<'a, ScreenDimensions>,
+        WriteStorage<'a, Camera>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, ( entities,
+                        time,
+                        input,
+                        active_camera,
+                        game_field,
+                        screen_dimensions,
+                        mut cameras,
+                        mut transforms,
+                        ): Self::SystemData) {
+
+        let board_size = game_field.world_size();
+        let screen_size = (screen_dimensions.width(), screen_dimensions.height());
+        if board_size.0 <= 0.0 || board_size.1 <= 0.0 || screen_size.0 <= 0.0 || screen_size.1 <= 0.0 {
+            return;
+        }
+
+        let mut view = match self.view {
+            Some(view) if view.board_size == board_size => View { screen_size, ..view },
+            _ => View {
+                screen_size,
+                board_size,
+                center: (board_size.0 * 0.5, board_size.1 * 0.5),
+                zoom: 1.0,
+            },
+        };
+        let fitted_size = fit_view(board_size, screen_size);
+        // World units per screen pixel before zooming in
+        let fitted_scale = fitted_size.0 / screen_size.0;
+
+        let wheel = input.mouse_wheel_value(false);
+        if wheel != 0.0 {
+            view.zoom = (view.zoom * ZOOM_STEP.powf(wheel)).max(1.0).min(MAX_ZOOM);
+        }
+        let world_per_pixel = fitted_scale / view.zoom;
+
+        let mut pan = (0.0, 0.0);
+        let key_down = |keys: [VirtualKeyCode; 2]| keys.iter().any(|key| input.key_is_down(*key));
+        if key_down([VirtualKeyCode::Left, VirtualKeyCode::A]) {
+            pan.0 -= 1.0;
+        }
+        if key_down([VirtualKeyCode::Right, VirtualKeyCode::D]) {
+            pan.0 += 1.0;
+        }
+        if key_down([VirtualKeyCode::Down, VirtualKeyCode::S]) {
+            pan.1 -= 1.0;
+        }
+        if key_down([VirtualKeyCode::Up, VirtualKeyCode::W]) {
+            pan.1 += 1.0;
+        }
+        let key_pan = PAN_SPEED * time.delta_seconds() * world_per_pixel;
+        view.center.0 += pan.0 * key_pan;
+        view.center.1 += pan.1 * key_pan;
+
+        // Dragging moves the board along with the mouse, screen y points down
+        let mouse_position = input.mouse_position();
+        if input.mouse_button_is_down(MouseButton::Middle) {
+            if let (Some(from), Some(to)) = (self.drag_from, mouse_position) {
+                view.center.0 -= (to.0 - from.0) * world_per_pixel;
+                view.center.1 += (to.1 - from.1) * world_per_pixel;
+            }
+            self.drag_from = mouse_position;
+        } else {
+            self.drag_from = None;
+        }
+
+        let view_size = (fitted_size.0 / view.zoom, fitted_size.1 / view.zoom);
+        view.center = clamp_center(view.center, view_size, board_size);
+        if self.view == Some(view) {
+            return;
+        }
+
+        let camera_entity = active_camera
+            .entity
+            .or_else(|| (&entities, &cameras).join().map(|(entity, _)| entity).next());
+        let camera_entity = match camera_entity {
+            Some(camera_entity) => camera_entity,
+            None => return,
+        };
+        if let Some(camera) = cameras.get_mut(camera_entity) {
+            *camera = Camera::standard_2d(view_size.0, view_size.1);
+        }
+        if let Some(transform) = transforms.get_mut(camera_entity) {
+            let z = transform.translation().z;
+            transform.set_translation_xyz(view.center.0, view.center.1, z);
+        }
+        self.view = Some(view);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_view_shows_the_whole_board_without_distortion() {
+        // A board smaller than the window is scaled up, filling the window in height
+        assert_eq!(fit_view((300.0, 300.0), (800.0, 600.0)), (400.0, 300.0));
+        // A board larger than the window is scaled down, filling the window in width
+        assert_eq!(fit_view((1600.0, 480.0), (800.0, 600.0)), (1600.0, 1200.0));
+        assert_eq!(fit_view((800.0, 600.0), (800.0, 600.0)), (800.0, 600.0));
+    }
+
+    #[test]
+    fn clamp_center_keeps_a_smaller_view_on_the_board() {
+        let board = (640.0, 480.0);
+        let view = (200.0, 100.0);
+        assert_eq!(clamp_center((320.0, 240.0), view, board), (320.0, 240.0));
+        assert_eq!(clamp_center((0.0, -50.0), view, board), (100.0, 50.0));
+        assert_eq!(clamp_center((1000.0, 1000.0), view, board), (540.0, 430.0));
+    }
+
+    #[test]
+    fn clamp_center_centers_a_larger_view_on_the_board() {
+        let board = (640.0, 480.0);
+        assert_eq!(clamp_center((0.0, 0.0), (800.0, 600.0), board), (320.0, 240.0));
+        // Only the axis where the view is larger than the board is centered
+        assert_eq!(clamp_center((0.0, 0.0), (800.0, 100.0), board), (320.0, 50.0));
+    }
+}
@@ -0,0 +1,303 @@
+//! The campaign: a network of battle nodes which unlock each other
+//!
+//! The `MapState` is the root state of the game. Choosing an unlocked node pushes the `Spybotics`
+//! state with the level of the node, and winning the battle unlocks the linked nodes. The Warez
+//! shop and the saved battle are reached from the map as well.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use amethyst::{
+    ecs::{Entity, World, WorldExt},
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    winit::VirtualKeyCode,
+};
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::objective::{BattleOutcome, BattleResult};
+use crate::pickup::Wallet;
+use crate::profile::save_profile;
+use crate::replay::Replay;
+use crate::savegame::load_battle_save;
+use crate::shop::ShopState;
+use crate::text::{create_label, delete_labels};
+use crate::turn::NUMBER_KEYS;
+use crate::Spybotics;
+
+/// A battle of the campaign
+#[derive(Debug, Clone, Deserialize)]
+pub struct CampaignNode {
+    pub id: String,
+    pub name: String,
+    /// File name of the level in `resource/levels/`
+    pub level: String,
+    /// Nodes unlocked by winning this battle
+    pub links: Vec<String>,
+}
+
+/// Resource holding the network of campaign nodes
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Campaign {
+    /// Nodes unlocked from the beginning
+    pub start: Vec<String>,
+    pub nodes: Vec<CampaignNode>,
+}
+
+impl Campaign {
+
+    /// Loads the campaign from a RON file
+    pub fn load(path: &Path) -> amethyst::Result<Self> {
+        let file = File::open(path)?;
+        let campaign: Campaign = ron::de::from_reader(file)?;
+        campaign.validate()?;
+        Ok(campaign)
+    }
+
+    /// Checks that the start nodes and the links refer to nodes of the campaign
+    fn validate(&self) -> amethyst::Result<()> {
+        let unknown = self.start
+            .iter()
+            .map(|id| ("the start", id))
+            .chain(self.nodes.iter().flat_map(|node| node.links.iter().map(move |link| (node.name.as_str(), link))))
+            .find(|(_, id)| self.node(id).is_none());
+        match unknown {
+            Some((referrer, id)) => Err(amethyst::Error::from_string(format!(
+                "The campaign node {} in {} does not exist",
+                id, referrer,
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn node(&self, id: &str) -> Option<&CampaignNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+}
+
+/// Resource tracking which nodes the player has unlocked and completed
+#[derive(Debug, Clone, Default)]
+pub struct CampaignProgress {
+    pub unlocked: Vec<String>,
+    pub completed: Vec<String>,
+}
+
+impl CampaignProgress {
+
+    /// The progress at the start of the campaign
+    pub fn new(campaign: &Campaign) -> Self {
+        CampaignProgress {
+            unlocked: campaign.start.clone(),
+            completed: Vec::new(),
+        }
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.iter().any(|unlocked| unlocked == id)
+    }
+
+    pub fn is_completed(&self, id: &str) -> bool {
+        self.completed.iter().any(|completed| completed == id)
+    }
+
+    /// Marks the node as completed and unlocks its links
+    pub fn complete(&mut self, node: &CampaignNode) {
+        if !self.is_completed(&node.id) {
+            self.completed.push(node.id.clone());
+        }
+        for link in node.links.iter() {
+            if !self.is_unlocked(link) {
+                info!("Unlocked {}", link);
+                self.unlocked.push(link.clone());
+            }
+        }
+    }
+}
+
+/// The campaign map, listing the nodes and starting their battles
+pub struct MapState {
+    /// Directory of the level files
+    levels_dir: PathBuf,
+    /// The node whose battle is running
+    battle_node: Option<CampaignNode>,
+    /// The replay to play back when the game starts
+    replay: Option<Replay>,
+    labels: Vec<Entity>,
+}
+
+impl MapState {
+
+    pub fn new(levels_dir: PathBuf) -> Self {
+        MapState {
+            levels_dir,
+            battle_node: None,
+            replay: None,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Starts the game with playing back the replay
+    pub fn replay(levels_dir: PathBuf, replay: Replay) -> Self {
+        MapState {
+            replay: Some(replay),
+            ..MapState::new(levels_dir)
+        }
+    }
+
+    fn show_map(&mut self, world: &mut World) {
+        let lines: Vec<String> = {
+            let campaign = world.read_resource::<Campaign>();
+            let progress = world.read_resource::<CampaignProgress>();
+            campaign.nodes
+                .iter()
+                .enumerate()
+                .map(|(index, node)| {
+                    let status = if progress.is_completed(&node.id) {
+                        "completed"
+                    } else if progress.is_unlocked(&node.id) {
+                        "open"
+                    } else {
+                        "locked"
+                    };
+                    let links: Vec<String> = node.links
+                        .iter()
+                        .filter_map(|link| campaign.node(link))
+                        .map(|link| match progress.is_unlocked(&link.id) {
+                            true => link.name.clone(),
+                            false => format!("{} (locked)", link.name),
+                        })
+                        .collect();
+                    match links.is_empty() {
+                        true => format!("[{}] {} ({})", index + 1, node.name, status),
+                        false => format!("[{}] {} ({}) -> {}", index + 1, node.name, status, links.join(", ")),
+                    }
+                })
+                .collect()
+        };
+
+        let credits = world.read_resource::<Wallet>().credits;
+        let has_saved_battle = load_battle_save(world).is_some();
+
+        self.labels.push(create_label(world, "map_title", "Network Map".to_string(), 200.0, 40.0));
+        self.labels.push(create_label(world, "map_credits", format!("{} credits", credits), 160.0, 20.0));
+        for (index, line) in lines.into_iter().enumerate() {
+            let id = format!("map_node_{}", index);
+            self.labels.push(create_label(world, &id, line, 120.0 - 40.0 * index as f32, 24.0));
+        }
+        if has_saved_battle {
+            let line = "[R] Resume the saved battle".to_string();
+            self.labels.push(create_label(world, "map_resume", line, -200.0, 24.0));
+        }
+        let hint = "Choose a node with the number keys, W for the Warez shop, escape to quit".to_string();
+        self.labels.push(create_label(world, "map_hint", hint, -240.0, 18.0));
+    }
+
+    /// Starts the saved battle again, at the turn it was saved in
+    fn resume_battle(&mut self, world: &mut World) -> SimpleTrans {
+        let saved = match load_battle_save(world) {
+            Some(saved) => saved,
+            None => {
+                info!("There is no saved battle to resume");
+                return Trans::None;
+            }
+        };
+        let node = world.read_resource::<Campaign>()
+            .nodes
+            .iter()
+            .find(|node| node.level == saved.level)
+            .cloned();
+        info!("Resuming the battle in {} at turn {}", saved.level, saved.turn);
+        let battle = Spybotics::resume(self.levels_dir.clone(), saved);
+        self.battle_node = node;
+        Trans::Push(Box::new(battle))
+    }
+}
+
+impl SimpleState for MapState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.show_map(data.world);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(node) = self.battle_node.take() {
+            let result = data.world.read_resource::<BattleOutcome>().result;
+            if result == Some(BattleResult::Victory) {
+                data.world.write_resource::<CampaignProgress>().complete(&node);
+            }
+            save_profile(data.world);
+        }
+        self.show_map(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        save_profile(data.world);
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn update(&mut self, _data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        match self.replay.take() {
+            Some(replay) => Trans::Push(Box::new(Spybotics::replay(self.levels_dir.clone(), replay))),
+            None => Trans::None,
+        }
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) || is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::W) {
+                return Trans::Push(Box::new(ShopState::default()));
+            }
+            if is_key_down(&event, VirtualKeyCode::R) {
+                return self.resume_battle(data.world);
+            }
+            if let Some(index) = NUMBER_KEYS.iter().position(|key| is_key_down(&event, *key)) {
+                let node = data.world.read_resource::<Campaign>().nodes.get(index).cloned();
+                let node = match node {
+                    Some(node) => node,
+                    None => return Trans::None,
+                };
+                if !data.world.read_resource::<CampaignProgress>().is_unlocked(&node.id) {
+                    warn!("{} is still locked", node.name);
+                    return Trans::None;
+                }
+                info!("Starting the battle at {}", node.name);
+                let battle = Spybotics::new(self.levels_dir.clone(), &node.level);
+                self.battle_node = Some(node);
+                return Trans::Push(Box::new(battle));
+            }
+        }
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn unknown_links_are_rejected() {
+        let campaign: Campaign = ron::de::from_str(
+            "(start: [\"a\"], nodes: [(id: \"a\", name: \"A\", level: \"a.ron\", links: [\"b\"])])",
+        ).unwrap();
+        assert!(campaign.validate().is_err());
+        let campaign: Campaign = ron::de::from_str(
+            "(start: [\"b\"], nodes: [(id: \"a\", name: \"A\", level: \"a.ron\", links: [])])",
+        ).unwrap();
+        assert!(campaign.validate().is_err());
+    }
+
+    #[test]
+    fn shipped_campaign_is_valid() {
+        Campaign::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("resource/config/campaign.ron")).unwrap();
+    }
+}
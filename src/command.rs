@@ -0,0 +1,273 @@
+//! Execution of program commands
+
+use std::fmt;
+
+use amethyst::ecs::Entity;
+
+use crate::action::Battlefield;
+use crate::body::ProgramBody;
+use crate::grid::distance;
+use crate::program::CommandEffect;
+
+/// What an executed command did to its target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandOutcome {
+    /// The target lost the given number of sectors
+    Damaged { target: String, sectors: usize },
+    /// The target lost all its sectors and got deleted
+    Deleted { target: String },
+    /// The tile at the given grid position became a void
+    TileDeleted { grid_position: (u32, u32) },
+    /// The void at the given grid position became a tile again
+    TileRestored { grid_position: (u32, u32) },
+}
+
+impl fmt::Display for CommandOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandOutcome::Damaged { target, sectors } => write!(f, "{} lost {} sector(s)", target, sectors),
+            CommandOutcome::Deleted { target } => write!(f, "{} got deleted", target),
+            CommandOutcome::TileDeleted { grid_position } => write!(f, "tile {:?} got deleted", grid_position),
+            CommandOutcome::TileRestored { grid_position } => write!(f, "tile {:?} got restored", grid_position),
+        }
+    }
+}
+
+/// Why a command could not be executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The program executing the command is not on the field anymore
+    NoUser,
+    /// The program has no command with the given index
+    UnknownCommand,
+    /// The program has already finished its turn
+    AlreadyDone,
+    /// The target is further away from the head than the range of the command
+    OutOfRange,
+    /// There is no program at the target
+    NoTarget,
+    /// The target is a program of the same side
+    FriendlyTarget,
+    /// The target is outside of the field or a void
+    NoTile,
+    /// The target tile is occupied by a program or holds a pickup
+    TileOccupied,
+    /// The target tile is not a void
+    NotVoid,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            CommandError::NoUser => "the program is not on the field",
+            CommandError::UnknownCommand => "unknown command",
+            CommandError::AlreadyDone => "program has already finished its turn",
+            CommandError::OutOfRange => "target out of range",
+            CommandError::NoTarget => "no program at the target",
+            CommandError::FriendlyTarget => "target is a friendly program",
+            CommandError::NoTile => "no tile at the target",
+            CommandError::TileOccupied => "target tile is occupied",
+            CommandError::NotVoid => "target tile is not a void",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Resource holding the result of the last executed command, so it can be reported to the player
+#[derive(Debug, Default)]
+pub struct CommandReport {
+    pub last: Option<Result<CommandOutcome, CommandError>>,
+}
+
+/// Checks whether `user` can execute its command `command` on the grid position `target`.
+///
+/// `target_body` is the program occupying the target, `target_walkable` whether the tile at the
+/// target is walkable (`None` if the target lies outside of the field) and `target_pickup` whether
+/// a pickup lies on it.
+pub fn validate(user: &ProgramBody, command: usize, target: (u32, u32), target_body: Option<&ProgramBody>, target_walkable: Option<bool>, target_pickup: bool) -> Result<(), CommandError> {
+    let command = user.program.commands.get(command).ok_or(CommandError::UnknownCommand)?;
+    if user.is_done() {
+        return Err(CommandError::AlreadyDone);
+    }
+    if distance(user.head(), target) > command.range {
+        return Err(CommandError::OutOfRange);
+    }
+    match command.effect {
+        CommandEffect::Damage(_) => {
+            let target_body = target_body.ok_or(CommandError::NoTarget)?;
+            if target_body.side == user.side {
+                return Err(CommandError::FriendlyTarget);
+            }
+        }
+        CommandEffect::DeleteTile => {
+            if target_walkable != Some(true) {
+                return Err(CommandError::NoTile);
+            }
+            // Deleting the tile would leave its pickup floating over the void
+            if target_body.is_some() || target_pickup {
+                return Err(CommandError::TileOccupied);
+            }
+        }
+        CommandEffect::RestoreTile => {
+            match target_walkable {
+                None => return Err(CommandError::NoTile),
+                Some(true) => return Err(CommandError::NotVoid),
+                Some(false) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Executes the command `command` of the program `user` on the grid position `target`.
+///
+/// Executing a command ends the turn of the program. Programs which lose all their sectors are
+/// deleted from the field.
+pub fn execute<B: Battlefield + ?Sized>(field: &mut B, user: Entity, command: usize, target: (u32, u32)) -> Result<CommandOutcome, CommandError> {
+    let target_entity = field.occupant(target);
+
+    let effect = {
+        let user_body = field.program(user).ok_or(CommandError::NoUser)?;
+        let target_body = target_entity.and_then(|entity| field.program(entity));
+        validate(user_body, command, target, target_body, field.is_walkable(target), field.has_pickup(target))?;
+        user_body.program.commands[command].effect
+    };
+
+    let outcome = match effect {
+        CommandEffect::Damage(damage) => {
+            let target_entity = target_entity.ok_or(CommandError::NoTarget)?;
+            let target_body = field.program_mut(target_entity).ok_or(CommandError::NoTarget)?;
+            let sectors = target_body.remove_sectors(damage as usize);
+            let name = target_body.program.name.clone();
+            if target_body.size() == 0 {
+                field.delete_program(target_entity);
+                CommandOutcome::Deleted { target: name }
+            } else {
+                CommandOutcome::Damaged { target: name, sectors }
+            }
+        }
+        CommandEffect::DeleteTile => {
+            field.set_walkable(target, false);
+            CommandOutcome::TileDeleted { grid_position: target }
+        }
+        CommandEffect::RestoreTile => {
+            field.set_walkable(target, true);
+            CommandOutcome::TileRestored { grid_position: target }
+        }
+    };
+
+    if let Some(user_body) = field.program_mut(user) {
+        user_body.end_turn();
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::{Builder, World, WorldExt};
+
+    use super::*;
+    use crate::action::WorldField;
+    use crate::body::Side;
+    use crate::grid::GameField;
+    use crate::pickup::Pickup;
+    use crate::program::test_program;
+    use crate::Walkable;
+
+    const SLICE: &str = "(name: \"Slice\", range: 1, effect: Damage(2))";
+
+    fn body(side: Side, sectors: &[(u32, u32)], commands: &str) -> ProgramBody {
+        ProgramBody::restore(test_program(2, 4, commands), side, sectors, 2, false)
+    }
+
+    /// A 3x3 field without voids and with the given programs
+    fn battle_world(bodies: Vec<ProgramBody>) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.register::<Walkable>();
+        world.register::<ProgramBody>();
+        world.register::<Pickup>();
+        let tiles = (0..9)
+            .map(|_| world.create_entity().with(Walkable::new(true)).build())
+            .collect();
+        world.insert(GameField::new((3, 3), tiles));
+        let programs = bodies
+            .into_iter()
+            .map(|body| world.create_entity().with(body).build())
+            .collect();
+        (world, programs)
+    }
+
+    /// Executes the command on the world, the way the `ActionSystem` does
+    fn execute_in(world: &mut World, user: Entity, command: usize, target: (u32, u32)) -> Result<CommandOutcome, CommandError> {
+        let result = {
+            let entities = world.entities();
+            let game_field = world.read_resource::<GameField>();
+            let mut walkable = world.write_storage::<Walkable>();
+            let mut bodies = world.write_storage::<ProgramBody>();
+            let pickups = world.read_storage::<Pickup>();
+            let mut field = WorldField {
+                entities: &entities,
+                game_field: &game_field,
+                walkable: &mut walkable,
+                bodies: &mut bodies,
+                pickups: &pickups,
+            };
+            execute(&mut field, user, command, target)
+        };
+        world.maintain();
+        result
+    }
+
+    #[test]
+    fn targets_out_of_range_are_rejected() {
+        let user = body(Side::Player, &[(0, 0)], SLICE);
+        let target = body(Side::Enemy, &[(1, 1)], "");
+        assert_eq!(validate(&user, 0, (1, 1), Some(&target), Some(true), false), Err(CommandError::OutOfRange));
+        assert_eq!(validate(&user, 0, (0, 1), Some(&target), Some(true), false), Ok(()));
+    }
+
+    #[test]
+    fn friendly_programs_are_no_targets() {
+        let user = body(Side::Player, &[(0, 0)], SLICE);
+        let friend = body(Side::Player, &[(0, 1)], "");
+        assert_eq!(validate(&user, 0, (0, 1), Some(&friend), Some(true), false), Err(CommandError::FriendlyTarget));
+        assert_eq!(validate(&user, 0, (0, 1), None, Some(true), false), Err(CommandError::NoTarget));
+    }
+
+    #[test]
+    fn tiles_holding_a_pickup_cannot_be_deleted() {
+        let user = body(Side::Player, &[(0, 0)], "(name: \"Zero\", range: 1, effect: DeleteTile)");
+        assert_eq!(validate(&user, 0, (0, 1), None, Some(true), false), Ok(()));
+        assert_eq!(validate(&user, 0, (0, 1), None, Some(true), true), Err(CommandError::TileOccupied));
+    }
+
+    #[test]
+    fn damage_truncates_the_tail_of_the_target() {
+        let (mut world, programs) = battle_world(vec![
+            body(Side::Player, &[(0, 0)], SLICE),
+            body(Side::Enemy, &[(0, 1), (1, 1), (2, 1)], ""),
+        ]);
+        let outcome = execute_in(&mut world, programs[0], 0, (0, 1));
+        assert_eq!(outcome, Ok(CommandOutcome::Damaged { target: "TEST".to_string(), sectors: 2 }));
+
+        let bodies = world.read_storage::<ProgramBody>();
+        let target: Vec<(u32, u32)> = bodies.get(programs[1]).unwrap().sectors().iter().copied().collect();
+        assert_eq!(target, vec![(0, 1)]);
+        assert!(bodies.get(programs[0]).unwrap().is_done());
+    }
+
+    #[test]
+    fn programs_losing_all_sectors_are_deleted() {
+        let (mut world, programs) = battle_world(vec![
+            body(Side::Player, &[(0, 0)], SLICE),
+            body(Side::Enemy, &[(0, 1), (1, 1)], ""),
+        ]);
+        let outcome = execute_in(&mut world, programs[0], 0, (1, 1));
+        assert_eq!(outcome, Err(CommandError::OutOfRange));
+
+        let outcome = execute_in(&mut world, programs[0], 0, (0, 1));
+        assert_eq!(outcome, Ok(CommandOutcome::Deleted { target: "TEST".to_string() }));
+        assert!(!world.is_alive(programs[1]));
+        assert!(world.read_storage::<ProgramBody>().get(programs[1]).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+//! Lookup of game tiles by their grid position and path finding on the grid
+
+use std::collections::{HashMap, VecDeque};
+
+use amethyst::{
+    core::Transform,
+    ecs::Entity,
+};
+
+use crate::GameTilePosition;
+
+/// Width and height of a tile in world units
+pub const TILE_SIZE: f32 = 32.0;
+
+/// Depth of the sprite of the tile itself
+pub const TILE_LAYER: f32 = -1.0;
+/// Depth of the sprites of pickups lying on a tile
+pub const PICKUP_LAYER: f32 = -0.75;
+/// Depth of the sprites of program sectors
+pub const PROGRAM_LAYER: f32 = -0.5;
+/// Depth of the selection squares
+pub const HIGHLIGHT_LAYER: f32 = 0.0;
+
+/// Resource mapping grid positions to the game tile entities
+#[derive(Debug, Default)]
+pub struct GameField {
+    extent: (u32, u32),
+    /// Tile entities, column by column
+    tiles: Vec<Entity>,
+}
+
+impl GameField {
+
+    pub fn new(extent: (u32, u32), tiles: Vec<Entity>) -> Self {
+        GameField {
+            extent,
+            tiles,
+        }
+    }
+
+    pub fn extent(&self) -> (u32, u32) {
+        self.extent
+    }
+
+    /// Width and height of the field in world units
+    pub fn world_size(&self) -> (f32, f32) {
+        (self.extent.0 as f32 * TILE_SIZE, self.extent.1 as f32 * TILE_SIZE)
+    }
+
+    /// Returns the tile entity at the given grid position, if it is inside the field
+    pub fn tile(&self, grid_position: (u32, u32)) -> Option<Entity> {
+        if grid_position.0 >= self.extent.0 || grid_position.1 >= self.extent.1 {
+            return None;
+        }
+        self.tiles
+            .get((grid_position.0 * self.extent.1 + grid_position.1) as usize)
+            .copied()
+    }
+}
+
+/// Creates the transform for a sprite centered on the given tile at depth `z`
+pub fn tile_sprite_transform(tile_position: &GameTilePosition, z: f32) -> Transform {
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(
+        tile_position.world_position.0 + tile_position.world_extent.0 * 0.5,
+        tile_position.world_position.1 + tile_position.world_extent.1 * 0.5,
+        z,
+    );
+    transform
+}
+
+/// Returns the grid positions next to the given one which lie inside a field of the given extent
+pub fn neighbours(grid_position: (u32, u32), extent: (u32, u32)) -> Vec<(u32, u32)> {
+    let (x, y) = grid_position;
+    let mut neighbours = Vec::with_capacity(4);
+    if x > 0 {
+        neighbours.push((x - 1, y));
+    }
+    if x + 1 < extent.0 {
+        neighbours.push((x + 1, y));
+    }
+    if y > 0 {
+        neighbours.push((x, y - 1));
+    }
+    if y + 1 < extent.1 {
+        neighbours.push((x, y + 1));
+    }
+    neighbours
+}
+
+/// Whether two grid positions share an edge
+pub fn adjacent(a: (u32, u32), b: (u32, u32)) -> bool {
+    distance(a, b) == 1
+}
+
+/// Manhattan distance between two grid positions
+pub fn distance(a: (u32, u32), b: (u32, u32)) -> u32 {
+    (a.0 as i64 - b.0 as i64).abs() as u32 + (a.1 as i64 - b.1 as i64).abs() as u32
+}
+
+/// Returns all grid positions inside the field within `range` of `center`, `center` excluded
+pub fn within_range(center: (u32, u32), range: u32, extent: (u32, u32)) -> Vec<(u32, u32)> {
+    let mut positions = Vec::new();
+    for x in center.0.saturating_sub(range)..(center.0 + range + 1).min(extent.0) {
+        for y in center.1.saturating_sub(range)..(center.1 + range + 1).min(extent.1) {
+            if (x, y) != center && distance(center, (x, y)) <= range {
+                positions.push((x, y));
+            }
+        }
+    }
+    positions
+}
+
+/// Breadth-first search over the passable grid positions, starting at `start` and taking at most
+/// `max_steps` steps.
+///
+/// Returns every reached grid position together with the position it was reached from. The start
+/// is reached from itself.
+pub fn reachable<F>(start: (u32, u32), max_steps: u32, extent: (u32, u32), passable: F) -> HashMap<(u32, u32), (u32, u32)>
+    where F: Fn((u32, u32)) -> bool
+{
+    let mut reached = HashMap::new();
+    reached.insert(start, start);
+
+    let mut frontier = VecDeque::new();
+    frontier.push_back((start, 0));
+
+    while let Some((grid_position, steps)) = frontier.pop_front() {
+        if steps == max_steps {
+            continue;
+        }
+        for next in neighbours(grid_position, extent) {
+            if !reached.contains_key(&next) && passable(next) {
+                reached.insert(next, grid_position);
+                frontier.push_back((next, steps + 1));
+            }
+        }
+    }
+    reached
+}
+
+/// Returns the path from the start of a search by `reachable` to `target`, both included
+pub fn path_to(reached: &HashMap<(u32, u32), (u32, u32)>, target: (u32, u32)) -> Option<Vec<(u32, u32)>> {
+    if !reached.contains_key(&target) {
+        return None;
+    }
+    let mut current = target;
+    let mut path = vec![current];
+    while let Some(previous) = reached.get(&current) {
+        if *previous == current {
+            break;
+        }
+        path.push(*previous);
+        current = *previous;
+    }
+    path.reverse();
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reachable_stops_at_the_move_budget() {
+        let reached = reachable((2, 2), 1, (5, 5), |_| true);
+        let mut positions: Vec<(u32, u32)> = reached.keys().copied().collect();
+        positions.sort();
+        assert_eq!(positions, vec![(1, 2), (2, 1), (2, 2), (2, 3), (3, 2)]);
+        assert_eq!(reachable((2, 2), 0, (5, 5), |_| true).len(), 1);
+    }
+
+    #[test]
+    fn reachable_stays_inside_the_field() {
+        let reached = reachable((0, 0), 1, (2, 2), |_| true);
+        assert_eq!(reached.len(), 3);
+        assert!(reached.keys().all(|(x, y)| *x < 2 && *y < 2));
+    }
+
+    #[test]
+    fn paths_lead_around_blocked_tiles_and_voids() {
+        // A wall at x = 1 with a gap at the top, (1, 2) being a void and (1, 1) occupied
+        let blocked = [(1, 0), (1, 1), (1, 2)];
+        let reached = reachable((0, 0), 10, (3, 4), |grid_position| !blocked.contains(&grid_position));
+        assert!(blocked.iter().all(|grid_position| !reached.contains_key(grid_position)));
+        assert_eq!(
+            path_to(&reached, (2, 0)),
+            Some(vec![(0, 0), (0, 1), (0, 2), (0, 3), (1, 3), (2, 3), (2, 2), (2, 1), (2, 0)]),
+        );
+        assert_eq!(path_to(&reached, (0, 0)), Some(vec![(0, 0)]));
+    }
+
+    #[test]
+    fn no_path_beyond_the_move_budget() {
+        let reached = reachable((0, 0), 2, (5, 1), |_| true);
+        assert_eq!(path_to(&reached, (2, 0)), Some(vec![(0, 0), (1, 0), (2, 0)]));
+        assert_eq!(path_to(&reached, (3, 0)), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! Undo and redo of the player's actions within the current turn
+//!
+//! Before the actions of a click are queued, the `SelectionSystem` records the programs and tiles
+//! as they are, along with the actions of the turn in the `Replay`. Undoing restores the recorded
+//! state, the sprite systems then follow the changed bodies and tiles. Commands which can not be
+//! undone and collected pickups lock the history, so the turn can only be undone back to them.
+
+use amethyst::ecs::{Entities, Entity, Join, ReadStorage, World, WorldExt};
+
+use log::{info, warn};
+
+use crate::action::{Action, ActionQueue};
+use crate::body::ProgramBody;
+use crate::replay::Replay;
+use crate::selection::Selection;
+use crate::Walkable;
+
+/// The programs and tiles at one point of the turn
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    bodies: Vec<(Entity, ProgramBody)>,
+    walkable: Vec<(Entity, bool)>,
+    /// Actions of the turn in the replay
+    actions: Vec<Action>,
+}
+
+impl Snapshot {
+
+    pub fn take(entities: &Entities<'_>, bodies: &ReadStorage<'_, ProgramBody>, walkable: &ReadStorage<'_, Walkable>, replay: &Replay) -> Self {
+        Snapshot {
+            bodies: (entities, bodies)
+                .join()
+                .map(|(entity, body)| (entity, body.clone()))
+                .collect(),
+            walkable: (entities, walkable)
+                .join()
+                .map(|(tile, tile_walkable)| (tile, tile_walkable.walkable))
+                .collect(),
+            actions: replay.turn_actions(),
+        }
+    }
+
+    fn of_world(world: &World) -> Self {
+        Snapshot::take(
+            &world.entities(),
+            &world.read_storage::<ProgramBody>(),
+            &world.read_storage::<Walkable>(),
+            &world.read_resource::<Replay>(),
+        )
+    }
+
+    fn restore(self, world: &mut World) {
+        let mut bodies = world.write_storage::<ProgramBody>();
+        for (entity, body) in self.bodies {
+            if bodies.insert(entity, body).is_err() {
+                warn!("Program to restore got deleted");
+            }
+        }
+        let mut walkable = world.write_storage::<Walkable>();
+        for (tile, tile_walkable) in self.walkable {
+            if let Some(tile) = walkable.get_mut(tile) {
+                tile.walkable = tile_walkable;
+            }
+        }
+        world.write_resource::<Replay>().set_turn_actions(self.actions);
+    }
+}
+
+/// Resource of the states the player can go back and forth to in the current turn
+#[derive(Debug, Default)]
+pub struct TurnHistory {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl TurnHistory {
+
+    /// Records the state before a new action, which discards the undone actions
+    pub fn record(&mut self, snapshot: Snapshot) {
+        self.undo.push(snapshot);
+        self.redo.clear();
+    }
+
+    /// Forgets all recorded states, after something which can not be undone
+    pub fn lock(&mut self) {
+        if !self.undo.is_empty() || !self.redo.is_empty() {
+            info!("The actions so far can not be undone anymore");
+        }
+        self.clear();
+    }
+
+    /// Drops the state recorded before an action which failed, so undo does not step back to it
+    pub fn discard(&mut self) {
+        self.undo.pop();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Returns to the state before the last action of the player
+pub fn undo(world: &mut World) {
+    if !world.read_resource::<ActionQueue>().is_empty() {
+        return;
+    }
+    let snapshot = match world.write_resource::<TurnHistory>().undo.pop() {
+        Some(snapshot) => snapshot,
+        None => {
+            info!("Nothing to undo");
+            return;
+        }
+    };
+    let current = Snapshot::of_world(world);
+    world.write_resource::<TurnHistory>().redo.push(current);
+    snapshot.restore(world);
+    world.write_resource::<Selection>().command = None;
+    info!("Undid the last action");
+}
+
+/// Applies the last undone action again
+pub fn redo(world: &mut World) {
+    if !world.read_resource::<ActionQueue>().is_empty() {
+        return;
+    }
+    let snapshot = match world.write_resource::<TurnHistory>().redo.pop() {
+        Some(snapshot) => snapshot,
+        None => {
+            info!("Nothing to redo");
+            return;
+        }
+    };
+    let current = Snapshot::of_world(world);
+    world.write_resource::<TurnHistory>().undo.push(current);
+    snapshot.restore(world);
+    world.write_resource::<Selection>().command = None;
+    info!("Redid the last undone action");
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::Builder;
+
+    use super::*;
+    use crate::body::Side;
+    use crate::program::test_program;
+
+    /// A world with a program at (0, 0) on a walkable tile, returning the program and the tile
+    fn world() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        world.register::<ProgramBody>();
+        world.register::<Walkable>();
+        world.insert(ActionQueue::default());
+        world.insert(TurnHistory::default());
+        world.insert(Selection::default());
+        world.insert(Replay::default());
+        let mut body = ProgramBody::new(test_program(3, 4, ""), Side::Player, (0, 0));
+        body.begin_turn();
+        let program = world.create_entity().with(body).build();
+        let tile = world.create_entity().with(Walkable::new(true)).build();
+        (world, program, tile)
+    }
+
+    fn head(world: &World, program: Entity) -> (u32, u32) {
+        world.read_storage::<ProgramBody>().get(program).unwrap().head()
+    }
+
+    /// Records the state, then moves the program one tile up
+    fn step_up(world: &mut World, program: Entity) {
+        let snapshot = Snapshot::of_world(world);
+        world.write_resource::<TurnHistory>().record(snapshot);
+        let mut bodies = world.write_storage::<ProgramBody>();
+        let body = bodies.get_mut(program).unwrap();
+        let (column, row) = body.head();
+        body.step((column, row + 1));
+    }
+
+    #[test]
+    fn undo_then_redo_returns_to_the_same_state() {
+        let (mut world, program, tile) = world();
+        step_up(&mut world, program);
+        world.write_storage::<Walkable>().get_mut(tile).unwrap().walkable = false;
+
+        undo(&mut world);
+        assert_eq!(head(&world, program), (0, 0));
+        assert!(world.read_storage::<Walkable>().get(tile).unwrap().walkable);
+        assert_eq!(world.read_storage::<ProgramBody>().get(program).unwrap().moves_left(), 3);
+
+        redo(&mut world);
+        assert_eq!(head(&world, program), (0, 1));
+        assert!(!world.read_storage::<Walkable>().get(tile).unwrap().walkable);
+        assert_eq!(world.read_storage::<ProgramBody>().get(program).unwrap().moves_left(), 2);
+    }
+
+    #[test]
+    fn recording_clears_the_redo_states() {
+        let (mut world, program, _) = world();
+        step_up(&mut world, program);
+        undo(&mut world);
+        assert_eq!(world.read_resource::<TurnHistory>().redo.len(), 1);
+
+        step_up(&mut world, program);
+        assert!(world.read_resource::<TurnHistory>().redo.is_empty());
+        redo(&mut world);
+        assert_eq!(head(&world, program), (0, 1));
+    }
+
+    #[test]
+    fn lock_forgets_undo_and_redo_states() {
+        let (mut world, program, _) = world();
+        step_up(&mut world, program);
+        step_up(&mut world, program);
+        undo(&mut world);
+
+        world.write_resource::<TurnHistory>().lock();
+        let history = world.read_resource::<TurnHistory>();
+        assert!(history.undo.is_empty());
+        assert!(history.redo.is_empty());
+    }
+
+    #[test]
+    fn discard_drops_the_state_before_a_failed_action() {
+        let (mut world, program, _) = world();
+        step_up(&mut world, program);
+        let snapshot = Snapshot::of_world(&world);
+        world.write_resource::<TurnHistory>().record(snapshot);
+        world.write_resource::<TurnHistory>().discard();
+
+        undo(&mut world);
+        assert_eq!(head(&world, program), (0, 0));
+    }
+}
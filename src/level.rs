@@ -0,0 +1,214 @@
+//! Layout of the battles
+
+use amethyst::{
+    assets::{Asset, Handle},
+    ecs::{Component, DenseVecStorage, VecStorage},
+};
+
+use serde::Deserialize;
+
+use crate::body::Side;
+use crate::objective::Condition;
+use crate::pickup::Pickup;
+use crate::spriteIds;
+
+/// Component of a tile on which programs can be uploaded before the battle starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct UploadZone {
+    pub side: Side,
+    /// Which of the `UPLOADZONE1` to `UPLOADZONE3` sprites marks the zone
+    pub variant: u8,
+}
+
+impl UploadZone {
+
+    pub fn sprite(&self) -> usize {
+        match self.variant {
+            1 => spriteIds::UPLOADZONE1,
+            2 => spriteIds::UPLOADZONE2,
+            3 => spriteIds::UPLOADZONE3,
+            _ => spriteIds::UPLOADZONE,
+        }
+    }
+}
+
+impl Component for UploadZone {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Resource describing the battle to set up.
+///
+/// Levels are RON files in `resource/levels/`, loaded through the asset `Loader` and checked
+/// with `validate` before the field is built.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// Number of columns and rows of the game field
+    pub extent: (u32, u32),
+    /// Grid positions without a tile
+    #[serde(default)]
+    pub void_cells: Vec<(u32, u32)>,
+    /// Shape of the field as rows of characters from the top row down, `.` or a missing
+    /// character marking a void. Ignored when empty.
+    #[serde(default)]
+    pub layout: Vec<String>,
+    pub upload_zones: Vec<((u32, u32), UploadZone)>,
+    /// Enemy programs by name and the grid position of their head
+    pub enemies: Vec<(String, (u32, u32))>,
+    pub pickups: Vec<((u32, u32), Pickup)>,
+    /// The battle is won as soon as one of these conditions is met
+    pub victory: Vec<Condition>,
+    /// The battle is lost as soon as one of these conditions is met
+    pub defeat: Vec<Condition>,
+    /// Seed of the random numbers of the battle, a new one is taken for every battle without it
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl Asset for Level {
+    const NAME: &'static str = "spybotics::Level";
+    type Data = Self;
+    type HandleStorage = VecStorage<Handle<Self>>;
+}
+
+impl Level {
+
+    /// Checks that the layout fits the extent and that everything placed on the field lies on a
+    /// tile of the field
+    pub fn validate(&self) -> amethyst::Result<()> {
+        let invalid = |reason: String| Err(amethyst::Error::from_string(format!("Level {}: {}", self.name, reason)));
+        if self.extent.0 == 0 || self.extent.1 == 0 {
+            return invalid(format!("the field of {}x{} tiles is empty", self.extent.0, self.extent.1));
+        }
+        if self.layout.len() > self.extent.1 as usize {
+            return invalid(format!("the layout has {} rows, the field {}", self.layout.len(), self.extent.1));
+        }
+        if let Some(row) = self.layout.iter().find(|row| row.chars().count() > self.extent.0 as usize) {
+            return invalid(format!("the layout row \"{}\" is wider than {} columns", row, self.extent.0));
+        }
+        if let Some(void) = self.void_cells.iter().find(|void| !self.contains(**void)) {
+            return invalid(format!("the void {:?} lies outside of the field", void));
+        }
+
+        let placements = self.upload_zones
+            .iter()
+            .map(|(grid_position, zone)| (format!("the {:?} upload zone", zone.side), *grid_position))
+            .chain(self.enemies.iter().map(|(name, grid_position)| (format!("the enemy {}", name), *grid_position)))
+            .chain(self.pickups.iter().map(|(grid_position, pickup)| (format!("the pickup {:?}", pickup), *grid_position)));
+        for (placed, grid_position) in placements {
+            if !self.contains(grid_position) {
+                return invalid(format!("{} at {:?} lies outside of the field", placed, grid_position));
+            }
+            if self.is_void(grid_position) {
+                return invalid(format!("{} at {:?} lies on a void", placed, grid_position));
+            }
+        }
+        Ok(())
+    }
+
+    fn contains(&self, grid_position: (u32, u32)) -> bool {
+        grid_position.0 < self.extent.0 && grid_position.1 < self.extent.1
+    }
+
+    /// Whether there is no tile at the grid position, given by `void_cells` or the `layout`
+    pub fn is_void(&self, grid_position: (u32, u32)) -> bool {
+        let (column, row) = grid_position;
+        let void_in_layout = !self.layout.is_empty() && self.extent.1
+            .checked_sub(row + 1)
+            .and_then(|line| self.layout.get(line as usize))
+            .and_then(|line| line.chars().nth(column as usize))
+            .map_or(true, |cell| cell == '.');
+        void_in_layout || self.void_cells.contains(&grid_position)
+    }
+
+    pub fn upload_zone(&self, grid_position: (u32, u32)) -> Option<UploadZone> {
+        self.upload_zones
+            .iter()
+            .find(|(zone_position, _)| *zone_position == grid_position)
+            .map(|(_, zone)| *zone)
+    }
+
+    pub fn pickup(&self, grid_position: (u32, u32)) -> Option<Pickup> {
+        self.pickups
+            .iter()
+            .find(|(pickup_position, _)| *pickup_position == grid_position)
+            .map(|(_, pickup)| *pickup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    /// A 4x3 level with the given optional fields, which are written like in a level file
+    fn level(fields: &str) -> Result<Level, ron::de::Error> {
+        ron::de::from_str(&format!(
+            "(name: \"Test\", extent: (4, 3), upload_zones: [], enemies: [], pickups: [], victory: [], defeat: [], {})",
+            fields,
+        ))
+    }
+
+    #[test]
+    fn void_cells_are_voids() {
+        let level = level("void_cells: [(1, 2)]").unwrap();
+        assert!(level.is_void((1, 2)));
+        assert!(!level.is_void((2, 1)));
+    }
+
+    #[test]
+    fn layout_rows_start_at_the_top() {
+        let level = level("layout: [\"#..#\", \"##\", \"####\"]").unwrap();
+        // Top row
+        assert!(!level.is_void((0, 2)));
+        assert!(level.is_void((1, 2)));
+        assert!(!level.is_void((3, 2)));
+        // Middle row, the missing characters are voids
+        assert!(!level.is_void((1, 1)));
+        assert!(level.is_void((2, 1)));
+        // Bottom row
+        assert!(!level.is_void((2, 0)));
+    }
+
+    #[test]
+    fn missing_rows_of_the_layout_are_voids() {
+        let level = level("layout: [\"####\"]").unwrap();
+        assert!(!level.is_void((0, 2)));
+        assert!(level.is_void((0, 0)));
+    }
+
+    #[test]
+    fn required_fields_are_not_defaulted() {
+        let result: Result<Level, _> = ron::de::from_str(
+            "(name: \"Test\", upload_zones: [], enemies: [], pickups: [], victory: [], defeat: [])",
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn validate_rejects_placements_off_the_tiles() {
+        assert!(level("").unwrap().validate().is_ok());
+        let mut outside = level("").unwrap();
+        outside.enemies.push(("HACK".to_string(), (4, 0)));
+        assert!(outside.validate().is_err());
+        let mut on_void = level("void_cells: [(1, 1)]").unwrap();
+        on_void.pickups.push(((1, 1), Pickup::DataItem));
+        assert!(on_void.validate().is_err());
+        assert!(level("layout: [\"#####\"]").unwrap().validate().is_err());
+        assert!(level("void_cells: [(0, 3)]").unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn shipped_levels_are_valid() {
+        let levels_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("resource/levels");
+        for entry in fs::read_dir(levels_dir).unwrap() {
+            let path = entry.unwrap().path();
+            let level: Level = ron::de::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            if let Err(error) = level.validate() {
+                panic!("{}: {}", path.display(), error);
+            }
+        }
+    }
+}
@@ -0,0 +1,929 @@
+//! Demonstrates sprite z ordering
+//!
+//! Sprites are originally from <https://opengameart.org/content/bat-32x32>, edited to show
+//! layering and blending.
+
+
+use amethyst::{
+    assets::{AssetStorage, Completion, Handle, Loader, ProgressCounter, Directory, Processor, RonFormat},
+    core::{Hidden, Transform, TransformBundle,
+        geometry::Plane,
+        math::{Point2,Point3,Vector2,Vector3},
+    },
+    ecs::{Entity, Entities, Join, Read,ReadStorage, Write, WriteStorage,World, WorldExt,
+          System, SystemData, Component, DenseVecStorage, ReadExpect},
+    input::{InputBundle,InputHandler,StringBindings,get_mouse_button,is_close_requested, ElementState, Button},
+    prelude::*,
+    derive::SystemDesc,
+    renderer::{
+        camera::{Projection,ActiveCamera},
+        plugins::{RenderFlat2D, RenderToWindow},
+        types::DefaultBackend,
+        Camera, ImageFormat, RenderingBundle, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture, Transparent,
+    },
+    ui::{RenderUi, UiBundle},
+    utils::{application_dir,application_root_dir},
+    window::ScreenDimensions,
+    winit::VirtualKeyCode,
+    winit::MouseButton,
+
+};
+
+use log::{error, info};
+use std::{env, io, path};
+use std::{thread, time};
+use std::borrow::Borrow;
+use std::ops::Deref;
+use std::path::PathBuf;
+use rand::prelude::*;
+
+mod action;
+mod ai;
+mod board;
+mod body;
+mod camera;
+mod campaign;
+mod command;
+mod grid;
+mod history;
+mod level;
+mod objective;
+mod overlay;
+mod pickup;
+mod profile;
+mod program;
+mod replay;
+mod rng;
+mod save;
+mod savegame;
+mod selection;
+mod shop;
+#[cfg(feature = "empty")]
+pub mod simulate;
+mod spriteIds;
+mod sprite_names;
+mod text;
+mod tile;
+mod turn;
+mod upload;
+
+use action::{ActionQueue, ActionSystem};
+use ai::Difficulty;
+use body::{place_programs, BodySpriteSystem, ProgramBody, Side};
+use camera::{fit_view, CameraSystem};
+use campaign::{Campaign, MapState};
+use command::CommandReport;
+use grid::{GameField, TILE_SIZE};
+use level::{Level, UploadZone};
+use objective::{BattleOutcome, ObjectiveSystem};
+use overlay::{Overlay, OverlaySystem};
+use pickup::{pay_credits, BattleStats, Pickup, PickupSystem};
+use profile::{Profile, ProfileFile};
+use program::ProgramCatalog;
+use replay::{save_replay, Replay, ReplayDir, ReplayState};
+use rng::{GameRng, SeedOverride};
+use savegame::{delete_battle_save, BattleSaveFile, SavedBattle};
+use selection::{Selection, SelectionSystem};
+use shop::Shop;
+use tile::{TileSpriteSystem, FLOOR_SPRITE};
+use turn::{EnemyTurnState, Phase, PlayerTurnState, TurnState, UploadState};
+use upload::{clear_upload_zones, OwnedPrograms, UploadSystem};
+
+//For the meaning of 'static, see https://doc.rust-lang.org/1.9.0/book/lifetimes.html
+// static CONFIG_PATH: &'static str = "resource\\config\\display.ron";
+static DISPLAY_PATH: &'static str = "resource/config/display.ron";
+static ASSET_PATH: &'static str = "resource/spybotics-icons/";
+static SPRITE_SHEET_NAME: &'static str = "spritesheet_extended.png";
+static RON_FILE_NAME: &'static str = "spritesheet_extended.ron";
+static PROGRAMS_PATH: &'static str = "resource/config/programs.ron";
+static LEVELS_PATH: &'static str = "resource/levels/";
+/// Name of the asset source the levels are loaded from
+static LEVEL_SOURCE: &'static str = "levels";
+static CAMPAIGN_PATH: &'static str = "resource/config/campaign.ron";
+static SHOP_PATH: &'static str = "resource/config/shop.ron";
+static PROFILE_PATH: &'static str = "save/profile.ron";
+static BATTLE_SAVE_PATH: &'static str = "save/battle.ron";
+static REPLAYS_PATH: &'static str = "save/replays/";
+
+
+#[derive(Debug, Clone)]
+struct LoadedSpriteSheet {
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    sprite_count: u32,
+    sprite_rows: u32,
+    sprite_columns: u32,
+    sprite_w: u32,
+    sprite_h: u32,
+}
+
+/// Component to carry information about the position about a game tile
+/// Grid position contains the coordinates in the game field grid
+/// world position contains the coordinates of the lower left corner of the tile on the world
+/// world extent gives the size in y and x direction of the tile
+#[derive(Debug,Default)]
+pub struct GameTilePosition {
+    grid_position: (u32, u32),
+    world_position: (f32, f32),
+    world_extent: (f32, f32)
+}
+
+impl GameTilePosition {
+    fn is_inside(&self,world_coordinates:(f32, f32)) -> bool{
+        let (left,right,top,bottom) = {
+            (
+                self.world_position.0,
+                self.world_position.0 + self.world_extent.0,
+                self.world_position.1 + self.world_extent.1,
+                self.world_position.1,
+            )
+        };
+        world_coordinates.0 > left &&
+            world_coordinates.0 < right &&
+            world_coordinates.1 > bottom &&
+            world_coordinates.1 < top
+    }
+}
+
+impl Component for GameTilePosition {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Default)]
+pub struct GameTileSpriteStack {
+    sprite_stack: Vec<Entity>
+}
+
+impl Component for GameTileSpriteStack {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Default)]
+pub struct Walkable {
+    walkable: bool,
+}
+
+impl Walkable {
+
+    fn new(_walkable: bool) -> Self {
+        Walkable {
+            walkable: _walkable,
+        }
+    }
+
+}
+
+impl Component for Walkable {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Default,Clone)]
+pub struct HandleHandle {
+    sprite_sheet_handle: Option<Handle<SpriteSheet>>,
+}
+
+#[derive(Default)]
+struct Spybotics {
+    /// The camera entity
+    camera: Option<Entity>,
+    /// The bat entities. TODO: Think about if this can be removed.
+    entities: Vec<Entity>,
+    /// Whether or not to add the transparent component to the entities
+    pause: bool,
+
+    /// The game field matrix
+    game_field: Vec<Entity>,
+    /// Information about the loaded sprite sheet.
+    loaded_sprite_sheet: Option<Handle<SpriteSheet>>,
+    /// Z-axis position of the camera.
+    ///
+    /// The Z axis increases "out of the screen" if the camera faces the XY plane (i.e. towards the
+    /// origin from (0.0, 0.0, 1.0)). This is the default orientation, when no rotation is applied to the
+    /// camera's transform.
+    camera_z: f32,
+    /// Depth (Z-axis distance) that the camera can see.
+    ///
+    /// The camera cannot see things on the limits of its view, i.e. entities with the same Z
+    /// coordinate cannot be seen, and entities at `Z - camera_depth_vision` also cannot be seen.
+    /// Entities with Z coordinates between these limits are visible.
+    camera_depth_vision: f32,
+
+    /// Directory of the level files
+    levels_dir: PathBuf,
+    /// File name of the level to play
+    level_name: String,
+    /// The level, until it is loaded and inserted as resource
+    level_handle: Option<Handle<Level>>,
+    level_progress: ProgressCounter,
+    /// The saved battle to resume instead of starting the level from the beginning
+    saved: Option<SavedBattle>,
+    /// The replay to play back instead of letting the player fight
+    replay: Option<Replay>,
+    /// Whether the AI plays both sides without a window
+    simulated: bool,
+}
+
+impl SimpleState for Spybotics {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        world.insert(DenseVecStorage::<GameTilePosition>::default());
+        world.insert(TurnState {
+            simulated: self.simulated,
+            ..TurnState::default()
+        });
+        let roster = world.read_resource::<OwnedPrograms>().roster();
+        world.insert(roster);
+        world.insert(BattleStats::default());
+        world.insert(BattleOutcome::default());
+        world.insert(CommandReport::default());
+        world.write_resource::<BattleSaveFile>().level = self.level_name.clone();
+        world.insert(Replay::new(&self.level_name));
+
+        // Without a window there is nothing to draw
+        if !self.simulated {
+            self.loaded_sprite_sheet = Some(sprite_sheet(world));
+        }
+        self.level_handle = Some(self.load_level(world));
+
+        // //wait until the sprites are loaded
+        // let one_second = time::Duration::from_secs(1);
+        // thread::sleep(one_second);
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_,'_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            };
+
+                match get_mouse_button(&event) {
+
+
+                Some((MouseButton::Left, ElementState::Pressed)) => {
+                        // try_fetch returns a Option<Fetch<MyResource>>
+                        let fetched = data.world.try_fetch::<InputHandler<StringBindings>>();
+                        if let Some(fetched_resource) = fetched {
+                            //dereference Fetch<MyResource> to access data
+                            if let Some(mouse_position) = fetched_resource.mouse_position() {
+                                //TODO: do something with the mouse input
+                            } else {
+                                println!("Mouse Position not available.");
+                            }
+                        } else {
+                            println!("No InputHandler present in `World`");
+                        }
+                        /*
+                            self.pause = !self.pause;
+                            info!(
+                                "Animation paused {}",
+                                if self.pause {
+                                    "enabled"
+                                } else {
+                                    "paused"
+                                }
+                            );
+                        */
+                    }
+
+                _ => {}
+            };
+        }
+
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_,'_>>) -> SimpleTrans{
+        let StateData { world, .. } = data;
+        // if !self.pause {
+        //     self.draw_sprites(world);
+        // }
+
+        // Only called while no battle state is on top, i.e. before the battle and after its end
+        let phase = world.read_resource::<TurnState>().phase;
+        match phase {
+            Phase::Setup => self.set_up_battle(world),
+            Phase::Victory | Phase::Defeat => {
+                pay_credits(world);
+                save_replay(world);
+                delete_battle_save(world);
+                Trans::Pop
+            }
+            // A replay ended before its last turn or a simulation was called off, the turn states
+            // never return here
+            Phase::PlayerTurn | Phase::EnemyTurn => Trans::Pop,
+            _ => Trans::None,
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+        self.clear_field(world);
+    }
+}
+
+impl Spybotics {
+
+    fn new(levels_dir: PathBuf, level_name: &str) -> Self {
+        Spybotics {
+            camera: None,
+            entities: Vec::new(),
+            pause: false,
+            game_field: Vec::new(),
+            loaded_sprite_sheet: None,
+            camera_z: 0.0,
+            camera_depth_vision: 0.0,
+            levels_dir,
+            level_name: level_name.to_string(),
+            level_handle: None,
+            level_progress: ProgressCounter::new(),
+            saved: None,
+            replay: None,
+            simulated: false,
+        }
+    }
+
+    /// Resumes a saved battle
+    fn resume(levels_dir: PathBuf, saved: SavedBattle) -> Self {
+        let mut battle = Spybotics::new(levels_dir, &saved.level);
+        battle.saved = Some(saved);
+        battle
+    }
+
+    /// Plays back a replay
+    fn replay(levels_dir: PathBuf, replay: Replay) -> Self {
+        let mut battle = Spybotics::new(levels_dir, &replay.level);
+        battle.replay = Some(replay);
+        battle
+    }
+
+    /// Lets the AI play both sides of the level, without a window
+    #[cfg(feature = "empty")]
+    fn simulate(levels_dir: PathBuf, level_name: &str) -> Self {
+        let mut battle = Spybotics::new(levels_dir, level_name);
+        battle.simulated = true;
+        battle
+    }
+
+    /// Builds the field once the level is loaded, then starts the upload, continues the saved turn
+    /// or plays back the replay. A level which fails to load returns to the campaign map.
+    fn set_up_battle(&mut self, world: &mut World) -> SimpleTrans {
+        match self.level_progress.complete() {
+            Completion::Loading => Trans::None,
+            Completion::Failed => {
+                error!("Failed to load level {}", self.level_name);
+                for error in self.level_progress.errors() {
+                    error!("{}: {}", error.asset_name, error.error);
+                }
+                Trans::Pop
+            }
+            Completion::Complete => {
+                let level = {
+                    let levels = world.read_resource::<AssetStorage<Level>>();
+                    self.level_handle
+                        .as_ref()
+                        .and_then(|handle| levels.get(handle))
+                        .expect("Level is loaded")
+                        .clone()
+                };
+                if let Err(error) = level.validate() {
+                    error!("Failed to load level {}: {}", self.level_name, error);
+                    return Trans::Pop;
+                }
+                info!("Loaded level {} ({}x{})", level.name, level.extent.0, level.extent.1);
+                world.insert(level);
+
+                if !self.simulated {
+                    self.initialise_camera(world);
+                }
+                self.initialize_field(world);
+                let enemies = world.read_resource::<Level>().enemies.clone();
+                if let Some(saved) = self.saved.take() {
+                    info!("Random seed of the battle: {}", saved.rng.seed);
+                    saved.restore(world);
+                    match saved.side {
+                        Side::Player => Trans::Push(Box::new(PlayerTurnState::default())),
+                        Side::Enemy => Trans::Push(Box::new(EnemyTurnState::default())),
+                    }
+                } else if let Some(replay) = self.replay.take() {
+                    info!("Random seed of the battle: {}", replay.seed);
+                    world.insert(GameRng::new(replay.seed));
+                    place_programs(world, &enemies, Side::Enemy);
+                    place_programs(world, &replay.uploads, Side::Player);
+                    clear_upload_zones(world);
+                    Trans::Push(Box::new(ReplayState::new(&replay)))
+                } else {
+                    let level_seed = world.read_resource::<Level>().seed;
+                    let seed = world.read_resource::<SeedOverride>().battle_seed(level_seed);
+                    info!("Random seed of the battle: {}", seed);
+                    world.insert(GameRng::new(seed));
+                    world.write_resource::<Replay>().seed = seed;
+                    place_programs(world, &enemies, Side::Enemy);
+                    Trans::Push(Box::new(UploadState))
+                }
+            }
+        }
+    }
+    /// Deletes the tiles with their sprites, the programs and the camera when leaving the battle.
+    ///
+    /// The sprite systems may still remember some of the deleted sprites, they skip entities
+    /// which are not alive anymore.
+    fn clear_field(&mut self, world: &mut World) {
+        let mut battle_entities: Vec<Entity> = {
+            let entities = world.entities();
+            let game_tile_sprite_stack = world.read_storage::<GameTileSpriteStack>();
+            let bodies = world.read_storage::<ProgramBody>();
+            (&entities, &game_tile_sprite_stack)
+                .join()
+                .flat_map(|(tile, tile_stack)| tile_stack.sprite_stack.iter().copied().chain(std::iter::once(tile)))
+                .chain((&entities, &bodies).join().map(|(entity, _)| entity))
+                .collect()
+        };
+        battle_entities.extend(self.camera.take());
+        battle_entities.extend(self.entities.drain(..));
+        world
+            .delete_entities(&battle_entities)
+            .expect("Failed to delete battle entities.");
+
+        self.game_field.clear();
+        world.insert(GameField::default());
+        world.write_resource::<ActionQueue>().clear();
+        world.write_resource::<Overlay>().clear();
+        *world.write_resource::<Selection>() = Selection::default();
+    }
+
+    /// This method initialises a camera which will view our sprite.
+    fn initialise_camera(&mut self, world: &mut World) {
+
+        self.camera_z = 1.0;
+        self.camera_depth_vision = 5.0;
+
+        self.adjust_camera(world);
+    }
+
+    fn adjust_camera(&mut self, world: &mut World) {
+        if let Some(camera) = self.camera.take() {
+            world
+                .delete_entity(camera)
+                .expect("Failed to delete camera entity.");
+        }
+        let screen_size = {
+            let dim = world.read_resource::<ScreenDimensions>();
+            (dim.width(), dim.height())
+        };
+
+        // The whole board is shown with square tiles, the `CameraSystem` fits it again on resize
+        let extent = world.read_resource::<Level>().extent;
+        let board_size = (TILE_SIZE * extent.0 as f32, TILE_SIZE * extent.1 as f32);
+        let (width, height) = fit_view(board_size, screen_size);
+
+        let mut camera_transform = Transform::default();
+        camera_transform.set_translation_xyz(board_size.0 * 0.5, board_size.1 * 0.5, self.camera_z);
+        //camera_transform.set_translation_xyz(0.0,0.0, self.camera_z);
+
+        let camera = world
+            .create_entity()
+            .with(Camera::standard_2d(width, height))
+            .with(camera_transform)
+            // Define the view that the camera can see. It makes sense to keep the `near` value as
+            // 0.0, as this means it starts seeing anything that is 0 units in front of it. The
+            // `far` value is the distance the camera can see facing the origin.
+            .build();
+
+        self.camera = Some(camera);
+    }
+
+    // fn redraw_sprites(&mut self, world: &mut World) {
+    //     let &SpriteSheet {
+    //         sprites,
+    //         ..
+    //     } = self
+    //         .loaded_sprite_sheet
+    //         .as_ref()
+    //         .expect("Expected sprite sheet to be loaded.");
+    //
+    //
+    //     // Delete any existing entities
+    //     self.entities.drain(..).for_each(|entity| {
+    //         world
+    //             .delete_entity(entity)
+    //             .expect("Failed to delete entity.")
+    //     });
+    //
+    //     self.draw_sprites(world);
+    // }
+
+    fn draw_sprites(&mut self, world: &mut World) {
+        // let sprite_count = {
+        //     let asset_storage = world.read_resource::<AssetStorage<SpriteSheet>>();
+        //     asset_storage
+        //         .get(self.loaded_sprite_sheet.as_ref().unwrap())
+        //         .expect("Why is this so complicated????")
+        //         .sprites.len()
+        // };
+
+        // Delete any existing entities
+        self.entities.drain(..).for_each(|entity| {
+            world
+                .delete_entity(entity)
+                .expect("Failed to delete entity.")
+        });
+
+        let mut common_transform = Transform::default();
+        common_transform.set_translation_x(-350.0 * 0.5);
+        common_transform.set_translation_y(-350.0 * 0.5);
+
+        let cols = 10;
+        // Create an entity per sprite.
+        for i in 0..144 {
+
+            let mut sprite_transform = Transform::default();
+            let position = {
+                let mut random_gen = world.write_resource::<GameRng>();
+                (random_gen.gen_range(100.0,500.0), random_gen.gen_range(100.0,500.0))
+            };
+            // sprite_transform.set_translation_xyz((i % cols * 32) as f32, ((i / cols * 32) as f32), -1.0);
+            sprite_transform.set_translation_xyz(position.0, position.1, -1.0);
+
+            sprite_transform.concat(&common_transform);
+
+            let sprite_render = SpriteRender {
+                sprite_sheet: self.loaded_sprite_sheet.as_ref().unwrap().clone(),
+                sprite_number: i,
+            };
+
+            let entity_builder = world
+                .create_entity()
+                .with(sprite_render)
+                .with(sprite_transform);
+
+            self.entities.push(entity_builder.build());
+        }
+    }
+
+    fn initialize_field(&mut self, world: &mut World){
+
+        // Delete any existing entities TODO: do we need this?
+        self.entities.drain(..).for_each(|entity| {
+            world
+                .delete_entity(entity)
+                .expect("Failed to delete entity.")
+        });
+
+        self.game_field = Vec::new();
+
+        let (sprite_offset_w, sprite_offset_h) = (16.0,16.0); //TODO: rather than hardcoding, we should load this from the spritesheet itself
+        let mut common_transform = Transform::default();
+        common_transform.set_translation_x(sprite_offset_w);
+        common_transform.set_translation_y(sprite_offset_h);
+
+
+        world.insert(HandleHandle{
+            sprite_sheet_handle: self.loaded_sprite_sheet.clone(),
+        });
+
+        world.register::<UploadZone>();
+        world.register::<Pickup>();
+        let level = (*world.read_resource::<Level>()).clone();
+
+        for i in 0..level.extent.0 {
+            for j in 0..level.extent.1 {
+
+                let mut sprite_transform = Transform::default();
+                let world_pos = (i as f32 * TILE_SIZE, j as f32 * TILE_SIZE);
+                sprite_transform.set_translation_xyz(world_pos.0, world_pos.1, -1.0);
+
+                sprite_transform.concat(&common_transform);
+
+                // A resumed battle has its upload zones cleared already
+                let (upload_zone, is_void, pickup) = match &self.saved {
+                    Some(saved) => (None, saved.is_void((i, j)), saved.pickup((i, j))),
+                    None => (level.upload_zone((i, j)), level.is_void((i, j)), level.pickup((i, j))),
+                };
+
+                // Voids have no sprite, the `TileSpriteSystem` adds the floor once they are restored.
+                // A simulated battle has no sprite sheet.
+                let mut sprite_stack = GameTileSpriteStack {
+                    sprite_stack: Vec::new()
+                };
+                if let (false, Some(sprite_sheet)) = (is_void, &self.loaded_sprite_sheet) {
+                    let sprite_render = SpriteRender {
+                        sprite_sheet: sprite_sheet.clone(),
+                        sprite_number: upload_zone.map_or(FLOOR_SPRITE, |zone| zone.sprite()),
+                    };
+
+                    let sprite_entity_builder = world
+                        .create_entity()
+                        .with(sprite_render)
+                        .with(sprite_transform);
+
+                    //self.entities.push(entity_builder.build());
+
+                    sprite_stack.sprite_stack.push(sprite_entity_builder.build());
+                }
+
+                let position = GameTilePosition{
+                    grid_position: (i,j),
+                    world_position: world_pos.clone(),
+                    world_extent: (TILE_SIZE, TILE_SIZE)
+                };
+
+                let mut game_tile_builder = world.create_entity()
+                    .with(position)
+                    .with(sprite_stack)
+                    .with( Walkable::new(!is_void));
+
+                if let Some(zone) = upload_zone {
+                    game_tile_builder = game_tile_builder.with(zone);
+                }
+                if let Some(pickup) = pickup {
+                    game_tile_builder = game_tile_builder.with(pickup);
+                }
+
+
+                self.game_field.push(game_tile_builder.build());
+            }
+        }
+
+        world.insert(GameField::new(level.extent, self.game_field.clone()));
+    }
+
+    /// Starts loading the level, `set_up_battle` waits until it is loaded
+    fn load_level(&mut self, world: &mut World) -> Handle<Level> {
+        let mut loader = world.write_resource::<Loader>();
+        loader.add_source(LEVEL_SOURCE, Directory::new(&self.levels_dir));
+        loader.load_from(
+            self.level_name.as_str(),
+            RonFormat,
+            LEVEL_SOURCE,
+            &mut self.level_progress,
+            &world.read_resource::<AssetStorage<Level>>(),
+        )
+    }
+}
+
+/// Returns the sprite sheet of the `HandleHandle`, which is loaded only the first time
+fn sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
+    let loaded = world
+        .try_fetch::<HandleHandle>()
+        .and_then(|handle_handle| handle_handle.sprite_sheet_handle.clone());
+    match loaded {
+        Some(sprite_sheet) => sprite_sheet,
+        None => {
+            let sprite_sheet = load_sprite_sheet(world);
+            world.insert(HandleHandle {
+                sprite_sheet_handle: Some(sprite_sheet.clone()),
+            });
+            sprite_sheet
+        }
+    }
+}
+
+/// Loads and returns a handle to a sprite sheet.
+///
+/// The sprite sheet consists of two parts:
+///
+/// * texture: the pixel data
+/// * `SpriteSheet`: the layout information of the sprites on the image
+fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
+
+    let texture_handle = {
+        let loader = world.read_resource::<Loader>();
+        let texture_storage = world.read_resource::<AssetStorage<Texture>>();
+        loader.load(
+            SPRITE_SHEET_NAME,
+            ImageFormat::default(),
+            (),
+            &texture_storage,
+        )
+    };
+
+    let loader = world.read_resource::<Loader>();
+    loader.load(
+        RON_FILE_NAME,
+        SpriteSheetFormat(texture_handle),
+        (),
+        &world.read_resource::<AssetStorage<SpriteSheet>>(),
+    )
+}
+
+/// Resource holding the grid position of the tile clicked in this frame
+#[derive(Debug, Default)]
+pub struct TileClick {
+    pub grid_position: Option<(u32, u32)>,
+}
+
+/// Translates left clicks into the grid position of the clicked tile.
+///
+/// Clicks on voids are ignored, unless the selected program is about to restore a tile.
+#[derive(Default)]
+struct MainSystem {
+    /// Whether the left mouse button was down in the previous frame
+    was_pressed: bool,
+}
+
+impl<'a> System<'a> for MainSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Camera>,
+        Read<'a, InputHandler<StringBindings>>,
+        Read<'a, ActiveCamera>,
+        ReadExpect<'a, ScreenDimensions>,
+        ReadStorage<'a,Transform>,
+        ReadStorage<'a, GameTilePosition>,
+        ReadStorage<'a, Walkable>,
+        ReadStorage<'a, ProgramBody>,
+        Read<'a, Selection>,
+        Write<'a, TileClick>,
+    );
+
+    fn run(&mut self, ( entities,
+                        cameras,
+                        input,
+                        active_camera,
+                        screen_dimensions,
+                        transforms,
+                        game_tile_position,
+                        walkable,
+                        bodies,
+                        selection,
+                        mut tile_click,
+                        ): Self::SystemData){
+
+        tile_click.grid_position = None;
+
+        // Only react to the frame the button gets pressed, not as long as it is held
+        let pressed = input.button_is_down(Button::Mouse(MouseButton::Left));
+        let clicked = pressed && !self.was_pressed;
+        self.was_pressed = pressed;
+
+        // Get the mouse position if its available
+        if clicked {
+            if let Some(mouse_position) = input.mouse_position() {
+                // Get the active camera if it is spawned and ready
+                let mut camera_join = (&cameras, &transforms).join();
+                if let Some((camera, camera_transform)) = active_camera
+                    .entity
+                    .and_then(|a| camera_join.get(a, &entities))
+                    .or_else(|| camera_join.next())
+                {
+                    // creates a point with the screen coordinates of the mouse pointer
+                    let mouse_coordinate = Point3::new(
+                        mouse_position.0,
+                        mouse_position.1,
+                        camera_transform.translation().z,
+                    );
+                    let screen_dimensions_vector =
+                        Vector2::new(screen_dimensions.width(), screen_dimensions.height());
+                    // creates a point with the _world_ coordinates of the mouse pointer
+                    let world_coordinate = camera.projection().screen_to_world_point(
+                        mouse_coordinate,
+                        screen_dimensions_vector,
+                        camera_transform,
+                    );
+
+                    // Find the tile the mouse is currently inside
+                    let targets_voids = selection.targets_voids(&bodies);
+                    tile_click.grid_position = (&game_tile_position, &walkable)
+                        .join()
+                        .find(|(tile_position, _)| tile_position.is_inside((world_coordinate.x,world_coordinate.y)))
+                        .filter(|(_, tile_walkable)| tile_walkable.walkable || targets_voids)
+                        .map(|(tile_position, _)| tile_position.grid_position);
+                }
+            }
+        }
+    }
+}
+
+/// Reads the difficulty of the enemy programs from the `--difficulty <easy|normal|hard>` argument
+fn difficulty_from_args() -> amethyst::Result<Difficulty> {
+    let mut args = env::args().skip_while(|arg| arg != "--difficulty").skip(1);
+    match args.next() {
+        Some(name) => Difficulty::parse(&name)
+            .ok_or_else(|| amethyst::Error::from_string(format!("Unknown difficulty {}", name))),
+        None => Ok(Difficulty::default()),
+    }
+}
+
+/// Reads the seed of the random numbers from the `--seed <number>` argument
+fn seed_from_args() -> amethyst::Result<SeedOverride> {
+    let mut args = env::args().skip_while(|arg| arg != "--seed").skip(1);
+    let seed = match args.next() {
+        Some(seed) => Some(seed
+            .parse()
+            .map_err(|_| amethyst::Error::from_string(format!("Invalid seed {}", seed)))?),
+        None => None,
+    };
+    Ok(SeedOverride { seed })
+}
+
+/// Loads the replay given by the `--replay <file>` argument
+fn replay_from_args() -> amethyst::Result<Option<Replay>> {
+    let mut args = env::args().skip_while(|arg| arg != "--replay").skip(1);
+    match args.next() {
+        Some(file) => Replay::load(path::Path::new(&file))?
+            .map(Some)
+            .ok_or_else(|| amethyst::Error::from_string(format!("Replay {} not found", file))),
+        None => Ok(None),
+    }
+}
+
+/// The directory of the cargo manifest, the resources and saves are relative to it
+fn app_root() -> PathBuf {
+    path::PathBuf::from(env::var_os("CARGO_MANIFEST_DIR")
+        .expect("Could not find CARGO_MANIFEST_DIR env variable, pointing to cargo manifest"))
+}
+
+/// Adds the systems playing a battle, which run with and without a window
+fn battle_systems<'a, 'b>(game_data: GameDataBuilder<'a, 'b>) -> GameDataBuilder<'a, 'b> {
+    game_data
+        .with(Processor::<Level>::new(), "level_processor", &[])
+        .with(UploadSystem, "upload_system", &[])
+        .with(SelectionSystem, "selection_system", &["upload_system"])
+        .with(ActionSystem::default(), "action_system", &["selection_system"])
+        .with(PickupSystem::default(), "pickup_system", &["action_system"])
+        .with(ObjectiveSystem, "objective_system", &["action_system", "pickup_system"])
+}
+
+/// Starts the game in a window, on the campaign map or playing back the replay given with
+/// `--replay <file>`
+pub fn run() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let app_root = app_root();
+
+    let display_config_path = app_root.join(DISPLAY_PATH);
+    println!("{:?}",display_config_path.to_str());
+
+    let assets_dir = app_root.join(ASSET_PATH);
+    println!("{:?}",assets_dir.to_str());
+
+    let levels_dir = app_root.join(LEVELS_PATH);
+
+    let difficulty = difficulty_from_args()?;
+    info!("Enemy difficulty: {:?}", difficulty);
+    let seed_override = seed_from_args()?;
+
+    let program_catalog = ProgramCatalog::load(&app_root.join(PROGRAMS_PATH))?;
+    info!("Loaded {} program definitions", program_catalog.programs().len());
+
+    let campaign = Campaign::load(&app_root.join(CAMPAIGN_PATH))?;
+    let profile_file = ProfileFile { path: app_root.join(PROFILE_PATH) };
+    let profile = match Profile::load(&profile_file.path)? {
+        Some(profile) => {
+            info!("Loaded the profile from {}", profile_file.path.display());
+            profile
+        }
+        None => Profile::new(&campaign),
+    };
+    let (wallet, owned_programs, campaign_progress) = profile.into_resources();
+    let shop = Shop::load(&app_root.join(SHOP_PATH))?;
+
+    let game_data = GameDataBuilder::default()
+        .with_bundle(TransformBundle::new())?
+        .with_bundle(InputBundle::<StringBindings>::new())?
+        .with_bundle(UiBundle::<StringBindings>::new())?
+        .with_bundle(
+            RenderingBundle::<DefaultBackend>::new()
+                .with_plugin(
+                    RenderToWindow::from_config_path(display_config_path)?
+                        .with_clear([0.34, 0.36, 0.52, 1.0]),
+                )
+                .with_plugin(RenderFlat2D::default())
+                .with_plugin(RenderUi::default()),
+        )?
+        .with(CameraSystem::default(), "camera_system", &[])
+        .with(MainSystem::default(),"MainSystem", &["input_system", "camera_system"])
+        // The clicks are translated before the battle systems handle them
+        .with_barrier();
+    let game_data = battle_systems(game_data)
+        .with(BodySpriteSystem::default(), "body_sprite_system", &["action_system"])
+        .with(OverlaySystem::default(), "overlay_system", &["selection_system"])
+        .with(TileSpriteSystem::default(), "tile_sprite_system", &["action_system"]);
+
+    let map = match replay_from_args()? {
+        Some(replay) => MapState::replay(levels_dir, replay),
+        None => MapState::new(levels_dir),
+    };
+
+    let mut game = Application::build(assets_dir, map)?
+        .with_resource(program_catalog)
+        .with_resource(difficulty)
+        .with_resource(campaign)
+        .with_resource(campaign_progress)
+        .with_resource(shop)
+        .with_resource(owned_programs)
+        .with_resource(wallet)
+        .with_resource(profile_file)
+        .with_resource(BattleSaveFile {
+            path: app_root.join(BATTLE_SAVE_PATH),
+            level: String::new(),
+        })
+        .with_resource(ReplayDir { path: app_root.join(REPLAYS_PATH) })
+        .with_resource(seed_override)
+        .with_resource(GameRng::default())
+        .build(game_data)?;
+    game.run();
+
+    Ok(())
+}
\ No newline at end of file
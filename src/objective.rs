@@ -0,0 +1,161 @@
+//! Win and loss conditions of a battle
+
+use amethyst::ecs::{Join, Read, ReadStorage, System, Write};
+
+use serde::Deserialize;
+
+use crate::body::{ProgramBody, Side};
+use crate::level::Level;
+use crate::pickup::BattleStats;
+use crate::turn::{Phase, TurnState};
+
+/// A condition a level can use to end the battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Condition {
+    /// No enemy program is left
+    AllEnemiesDeleted,
+    /// No player program is left
+    AllPlayerProgramsDeleted,
+    /// The player collected at least the given number of data items
+    DataItemsCollected(u32),
+    /// The given number of turns has passed
+    TurnLimit(u32),
+}
+
+/// How a battle ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleResult {
+    Victory,
+    Defeat,
+}
+
+/// Resource holding the result of the battle, once a condition of the level is met
+#[derive(Debug, Default)]
+pub struct BattleOutcome {
+    pub result: Option<BattleResult>,
+}
+
+/// What the conditions are evaluated on
+pub struct BattleSummary {
+    pub player_programs: usize,
+    pub enemy_programs: usize,
+    pub data_items_collected: u32,
+    pub turn: u32,
+}
+
+impl BattleSummary {
+
+    pub fn new<'b, I>(bodies: I, battle_stats: &BattleStats, turn: u32) -> Self
+        where I: Iterator<Item = &'b ProgramBody>
+    {
+        let (mut player_programs, mut enemy_programs) = (0, 0);
+        for body in bodies {
+            match body.side {
+                Side::Player => player_programs += 1,
+                Side::Enemy => enemy_programs += 1,
+            }
+        }
+        BattleSummary {
+            player_programs,
+            enemy_programs,
+            data_items_collected: battle_stats.data_items_collected,
+            turn,
+        }
+    }
+}
+
+impl Condition {
+
+    pub fn is_met(&self, summary: &BattleSummary) -> bool {
+        match self {
+            Condition::AllEnemiesDeleted => summary.enemy_programs == 0,
+            Condition::AllPlayerProgramsDeleted => summary.player_programs == 0,
+            Condition::DataItemsCollected(count) => summary.data_items_collected >= *count,
+            Condition::TurnLimit(turns) => summary.turn > *turns,
+        }
+    }
+}
+
+/// Returns the result of the battle if one of the conditions of the level is met.
+///
+/// Defeat conditions are checked first, so a battle is lost if both sides meet a condition at
+/// the same time.
+pub fn evaluate(level: &Level, summary: &BattleSummary) -> Option<BattleResult> {
+    if level.defeat.iter().any(|condition| condition.is_met(summary)) {
+        Some(BattleResult::Defeat)
+    } else if level.victory.iter().any(|condition| condition.is_met(summary)) {
+        Some(BattleResult::Victory)
+    } else {
+        None
+    }
+}
+
+/// Evaluates the conditions of the level after every action during the battle
+#[derive(Default)]
+pub struct ObjectiveSystem;
+
+impl<'a> System<'a> for ObjectiveSystem {
+
+    type SystemData = (
+        Read<'a, Level>,
+        Read<'a, TurnState>,
+        Read<'a, BattleStats>,
+        Write<'a, BattleOutcome>,
+        ReadStorage<'a, ProgramBody>,
+    );
+
+    fn run(&mut self, (level, turn_state, battle_stats, mut battle_outcome, bodies): Self::SystemData) {
+        match turn_state.phase {
+            Phase::PlayerTurn | Phase::EnemyTurn => {}
+            _ => return,
+        }
+
+        let summary = BattleSummary::new((&bodies).join(), &battle_stats, turn_state.turn);
+        battle_outcome.result = evaluate(&level, &summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(victory: Vec<Condition>, defeat: Vec<Condition>) -> Level {
+        Level {
+            victory,
+            defeat,
+            ..Level::default()
+        }
+    }
+
+    fn summary(player_programs: usize, enemy_programs: usize, data_items_collected: u32, turn: u32) -> BattleSummary {
+        BattleSummary {
+            player_programs,
+            enemy_programs,
+            data_items_collected,
+            turn,
+        }
+    }
+
+    #[test]
+    fn defeat_takes_precedence_over_victory() {
+        let level = level(vec![Condition::AllEnemiesDeleted], vec![Condition::AllPlayerProgramsDeleted]);
+        assert_eq!(evaluate(&level, &summary(0, 0, 0, 3)), Some(BattleResult::Defeat));
+        assert_eq!(evaluate(&level, &summary(1, 0, 0, 3)), Some(BattleResult::Victory));
+        assert_eq!(evaluate(&level, &summary(1, 1, 0, 3)), None);
+    }
+
+    #[test]
+    fn turn_limit_is_met_once_the_turns_have_passed() {
+        let level = level(vec![], vec![Condition::TurnLimit(30)]);
+        assert_eq!(evaluate(&level, &summary(1, 1, 0, 30)), None);
+        assert_eq!(evaluate(&level, &summary(1, 1, 0, 31)), Some(BattleResult::Defeat));
+    }
+
+    #[test]
+    fn data_items_count_towards_the_victory() {
+        let level = level(vec![Condition::DataItemsCollected(2)], vec![Condition::AllPlayerProgramsDeleted]);
+        assert_eq!(evaluate(&level, &summary(1, 1, 1, 5)), None);
+        assert_eq!(evaluate(&level, &summary(1, 1, 2, 5)), Some(BattleResult::Victory));
+        assert_eq!(evaluate(&level, &summary(1, 1, 3, 5)), Some(BattleResult::Victory));
+    }
+}
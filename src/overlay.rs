@@ -0,0 +1,147 @@
+//! Selection squares drawn on top of the game tiles
+
+use amethyst::{
+    core::Transform,
+    ecs::{Entities, Entity, Read, ReadStorage, System, WriteStorage},
+    renderer::{palette::Srgba, resources::Tint, SpriteRender},
+};
+
+use crate::grid::{tile_sprite_transform, GameField, HIGHLIGHT_LAYER};
+use crate::{GameTilePosition, GameTileSpriteStack, HandleHandle};
+
+/// Color the sprites of dimmed highlights are tinted with
+const DIMMED_TINT: (f32, f32, f32, f32) = (0.4, 0.4, 0.4, 1.0);
+
+/// A highlighted tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Highlight {
+    pub grid_position: (u32, u32),
+    pub sprite_number: usize,
+    /// Dimmed highlights mark tiles which are shown but cannot be chosen
+    pub dimmed: bool,
+}
+
+impl Highlight {
+
+    pub fn new(grid_position: (u32, u32), sprite_number: usize) -> Self {
+        Highlight {
+            grid_position,
+            sprite_number,
+            dimmed: false,
+        }
+    }
+
+    pub fn dimmed(grid_position: (u32, u32), sprite_number: usize) -> Self {
+        Highlight {
+            grid_position,
+            sprite_number,
+            dimmed: true,
+        }
+    }
+}
+
+/// Resource listing the tiles to highlight
+#[derive(Debug, Default)]
+pub struct Overlay {
+    tiles: Vec<Highlight>,
+}
+
+impl Overlay {
+
+    pub fn set(&mut self, mut tiles: Vec<Highlight>) {
+        // keep the order stable, so the overlay is only redrawn if the tiles actually change
+        tiles.sort();
+        self.tiles = tiles;
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+    }
+}
+
+/// Keeps the highlight sprites in the tile sprite stacks in sync with the `Overlay`
+#[derive(Default)]
+pub struct OverlaySystem {
+    drawn_tiles: Vec<Highlight>,
+    sprites: Vec<((u32, u32), Entity)>,
+}
+
+impl<'a> System<'a> for OverlaySystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Overlay>,
+        Read<'a, HandleHandle>,
+        Read<'a, GameField>,
+        ReadStorage<'a, GameTilePosition>,
+        WriteStorage<'a, GameTileSpriteStack>,
+        WriteStorage<'a, SpriteRender>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Tint>,
+    );
+
+    fn run(&mut self, ( entities,
+                        overlay,
+                        sprite_sheet_handle,
+                        game_field,
+                        game_tile_position,
+                        mut game_tile_sprite_stack,
+                        mut sprites,
+                        mut transforms,
+                        mut tints,
+                        ): Self::SystemData) {
+
+        if overlay.tiles == self.drawn_tiles {
+            return;
+        }
+        let sprite_sheet = match sprite_sheet_handle.sprite_sheet_handle.as_ref() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        for (grid_position, sprite) in self.sprites.drain(..) {
+            if let Some(tile_stack) = game_field.tile(grid_position)
+                .and_then(|tile| game_tile_sprite_stack.get_mut(tile)) {
+                tile_stack.sprite_stack.retain(|stacked| *stacked != sprite);
+            }
+            if entities.is_alive(sprite) {
+                entities.delete(sprite).expect("Failed to delete highlight sprite.");
+            }
+        }
+
+        for highlight in overlay.tiles.iter() {
+            let tile = match game_field.tile(highlight.grid_position) {
+                Some(tile) => tile,
+                None => continue,
+            };
+            let sprite_transform = match game_tile_position.get(tile) {
+                Some(tile_position) => tile_sprite_transform(tile_position, HIGHLIGHT_LAYER),
+                None => continue,
+            };
+
+            let sprite_render = SpriteRender {
+                sprite_sheet: sprite_sheet.clone(),
+                sprite_number: highlight.sprite_number,
+            };
+
+            let sprite = entities
+                .build_entity()
+                .with(sprite_render, &mut sprites)
+                .with(sprite_transform, &mut transforms)
+                .build();
+
+            if highlight.dimmed {
+                let (red, green, blue, alpha) = DIMMED_TINT;
+                tints.insert(sprite, Tint(Srgba::new(red, green, blue, alpha)))
+                    .expect("Failed to tint highlight sprite.");
+            }
+
+            if let Some(tile_stack) = game_tile_sprite_stack.get_mut(tile) {
+                tile_stack.sprite_stack.push(sprite);
+            }
+            self.sprites.push((highlight.grid_position, sprite));
+        }
+
+        self.drawn_tiles = overlay.tiles.clone();
+    }
+}
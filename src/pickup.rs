@@ -0,0 +1,191 @@
+//! Pickups lying on the game tiles, collected by moving the head of a player program onto them
+//!
+//! Collected credits are counted in the `BattleStats` and only paid into the `Wallet` once the
+//! battle ends. A battle resumed from its save starts again with the credits collected until the
+//! save, and the pickups collected since lie on their tiles again.
+
+use std::collections::HashMap;
+
+use amethyst::{
+    core::Transform,
+    ecs::{Component, DenseVecStorage, Entities, Entity, Join, Read, ReadStorage, System, World, WorldExt, Write, WriteStorage},
+    renderer::SpriteRender,
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::body::{ProgramBody, Side};
+use crate::grid::{tile_sprite_transform, GameField, PICKUP_LAYER};
+use crate::history::TurnHistory;
+use crate::turn::TurnState;
+use crate::{spriteIds, GameTilePosition, GameTileSpriteStack, HandleHandle};
+
+/// Component of a tile holding something to collect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pickup {
+    /// A bundle of credits
+    Credits(u32),
+    /// A single coin worth the given credits
+    Coin(u32),
+    /// A data item, which counts towards the objectives of a level
+    DataItem,
+}
+
+impl Pickup {
+
+    pub fn sprite(&self) -> usize {
+        match self {
+            Pickup::Credits(_) => spriteIds::CREDITS,
+            Pickup::Coin(_) => spriteIds::COIN,
+            Pickup::DataItem => spriteIds::DATAITEM,
+        }
+    }
+}
+
+impl Component for Pickup {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Resource holding the credits of the player
+#[derive(Debug, Default)]
+pub struct Wallet {
+    pub credits: u32,
+}
+
+/// Resource counting what the player collected in the current battle
+#[derive(Debug, Default)]
+pub struct BattleStats {
+    pub credits_collected: u32,
+    pub data_items_collected: u32,
+}
+
+impl BattleStats {
+
+    /// Counts the collected pickup, returns the credits it was worth
+    pub fn collect(&mut self, pickup: Pickup) -> u32 {
+        match pickup {
+            Pickup::Credits(credits) | Pickup::Coin(credits) => {
+                self.credits_collected += credits;
+                credits
+            }
+            Pickup::DataItem => {
+                self.data_items_collected += 1;
+                0
+            }
+        }
+    }
+}
+
+/// Pays the credits collected in the battle into the wallet, once the battle has ended
+pub fn pay_credits(world: &World) {
+    // Credits collected in a replay are not earned again
+    if world.read_resource::<TurnState>().replaying {
+        return;
+    }
+    let credits = world.read_resource::<BattleStats>().credits_collected;
+    let mut wallet = world.write_resource::<Wallet>();
+    wallet.credits += credits;
+    info!("Earned {} credits in the battle, {} credits in total", credits, wallet.credits);
+}
+
+/// The grid positions at which pickups are collected: the heads of the player programs
+pub fn collecting_heads<'b, I>(bodies: I) -> Vec<(u32, u32)>
+    where I: Iterator<Item = &'b ProgramBody>
+{
+    bodies
+        .filter(|body| body.side == Side::Player)
+        .map(|body| body.head())
+        .collect()
+}
+
+/// Collects the pickups under the heads of player programs and keeps the pickup sprites in the
+/// tile sprite stacks
+#[derive(Default)]
+pub struct PickupSystem {
+    /// Pickup sprite of every tile with a pickup
+    sprites: HashMap<Entity, Entity>,
+}
+
+impl<'a> System<'a> for PickupSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, HandleHandle>,
+        Read<'a, GameField>,
+        Write<'a, BattleStats>,
+        Write<'a, TurnHistory>,
+        ReadStorage<'a, ProgramBody>,
+        ReadStorage<'a, GameTilePosition>,
+        WriteStorage<'a, Pickup>,
+        WriteStorage<'a, GameTileSpriteStack>,
+        WriteStorage<'a, SpriteRender>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, ( entities,
+                        sprite_sheet_handle,
+                        game_field,
+                        mut battle_stats,
+                        mut turn_history,
+                        bodies,
+                        game_tile_position,
+                        mut pickups,
+                        mut game_tile_sprite_stack,
+                        mut sprites,
+                        mut transforms,
+                        ): Self::SystemData) {
+
+        for head in collecting_heads((&bodies).join()) {
+            let pickup = match game_field.tile(head).and_then(|tile| pickups.remove(tile)) {
+                Some(pickup) => pickup,
+                None => continue,
+            };
+            let credits = battle_stats.collect(pickup);
+            if credits > 0 {
+                info!("Collected {} credits, {} in this battle", credits, battle_stats.credits_collected);
+            }
+            if pickup == Pickup::DataItem {
+                info!("Collected a data item, {} so far", battle_stats.data_items_collected);
+            }
+            turn_history.lock();
+        }
+
+        // Remove the sprites of collected pickups
+        let collected: Vec<Entity> = self.sprites
+            .keys()
+            .filter(|tile| !pickups.contains(**tile))
+            .copied()
+            .collect();
+        for tile in collected {
+            let sprite = self.sprites.remove(&tile).unwrap();
+            if let Some(tile_stack) = game_tile_sprite_stack.get_mut(tile) {
+                tile_stack.sprite_stack.retain(|stacked| *stacked != sprite);
+            }
+            if entities.is_alive(sprite) {
+                entities.delete(sprite).expect("Failed to delete pickup sprite.");
+            }
+        }
+
+        let sprite_sheet = match sprite_sheet_handle.sprite_sheet_handle.as_ref() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        for (tile, pickup, tile_position, tile_stack) in (&entities, &pickups, &game_tile_position, &mut game_tile_sprite_stack).join() {
+            if self.sprites.contains_key(&tile) {
+                continue;
+            }
+            let sprite = entities
+                .build_entity()
+                .with(SpriteRender {
+                    sprite_sheet: sprite_sheet.clone(),
+                    sprite_number: pickup.sprite(),
+                }, &mut sprites)
+                .with(tile_sprite_transform(tile_position, PICKUP_LAYER), &mut transforms)
+                .build();
+            tile_stack.sprite_stack.push(sprite);
+            self.sprites.insert(tile, sprite);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! The player profile, keeping credits, programs and campaign progress between sessions
+//!
+//! The profile is a versioned RON file in the save directory.
+
+use std::path::{Path, PathBuf};
+
+use amethyst::ecs::{World, WorldExt};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::campaign::{Campaign, CampaignProgress};
+use crate::pickup::Wallet;
+use crate::save;
+use crate::upload::{OwnedPrograms, RosterEntry};
+
+/// Version of the profile format written by this build
+const PROFILE_VERSION: u32 = 1;
+
+/// Everything the player keeps between sessions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub version: u32,
+    pub credits: u32,
+    pub owned_programs: Vec<RosterEntry>,
+    pub unlocked_nodes: Vec<String>,
+    pub completed_nodes: Vec<String>,
+}
+
+impl Profile {
+
+    /// The profile of a new player
+    pub fn new(campaign: &Campaign) -> Self {
+        let progress = CampaignProgress::new(campaign);
+        Profile {
+            version: PROFILE_VERSION,
+            credits: Wallet::default().credits,
+            owned_programs: OwnedPrograms::default().programs,
+            unlocked_nodes: progress.unlocked,
+            completed_nodes: progress.completed,
+        }
+    }
+
+    /// Takes the profile from the resources of the world
+    pub fn from_world(world: &World) -> Self {
+        let progress = world.read_resource::<CampaignProgress>();
+        Profile {
+            version: PROFILE_VERSION,
+            credits: world.read_resource::<Wallet>().credits,
+            owned_programs: world.read_resource::<OwnedPrograms>().programs.clone(),
+            unlocked_nodes: progress.unlocked.clone(),
+            completed_nodes: progress.completed.clone(),
+        }
+    }
+
+    /// Splits the profile into the resources holding it during the game
+    pub fn into_resources(self) -> (Wallet, OwnedPrograms, CampaignProgress) {
+        (
+            Wallet { credits: self.credits },
+            OwnedPrograms { programs: self.owned_programs },
+            CampaignProgress {
+                unlocked: self.unlocked_nodes,
+                completed: self.completed_nodes,
+            },
+        )
+    }
+
+    /// Loads the profile, or returns `None` if there is no profile file yet
+    pub fn load(path: &Path) -> amethyst::Result<Option<Self>> {
+        save::load(path, PROFILE_VERSION)
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        save::save(self, path)
+    }
+}
+
+/// Resource with the path of the profile file
+#[derive(Debug, Clone, Default)]
+pub struct ProfileFile {
+    pub path: PathBuf,
+}
+
+/// Saves the profile of the player to the profile file
+pub fn save_profile(world: &World) {
+    let path = world.read_resource::<ProfileFile>().path.clone();
+    match Profile::from_world(world).save(&path) {
+        Ok(()) => info!("Saved the profile to {}", path.display()),
+        Err(error) => warn!("Failed to save the profile to {}: {}", path.display(), error),
+    }
+}
@@ -0,0 +1,140 @@
+//! Program definitions
+//!
+//! Programs are not defined in code but in a RON catalog (see `resource/config/programs.ron`),
+//! so they can be added and rebalanced without recompiling.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::sprite_names::{deserialize_sprite, sprite_id};
+
+/// The sprites used for the tail sectors of a program, one per direction.
+///
+/// In the catalog the tail is given by its sprite family, e.g. `"HACK"` resolves to
+/// `HACKTAILUP`, `HACKTAILDOWN`, `HACKTAILLEFT` and `HACKTAILRIGHT`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TailSprites {
+    pub up: usize,
+    pub down: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl TryFrom<String> for TailSprites {
+    type Error = String;
+
+    fn try_from(family: String) -> Result<Self, Self::Error> {
+        let variant = |direction: &str| {
+            let name = format!("{}TAIL{}", family, direction);
+            sprite_id(&name).ok_or_else(|| format!("unknown tail sprite `{}`", name))
+        };
+        Ok(TailSprites {
+            up: variant("UP")?,
+            down: variant("DOWN")?,
+            left: variant("LEFT")?,
+            right: variant("RIGHT")?,
+        })
+    }
+}
+
+impl TailSprites {
+
+    /// Returns the sprite for the tail sector at `sector` which connects to the neighbouring
+    /// sector `towards`, i.e. the one closer to the head
+    pub fn towards(&self, sector: (u32, u32), towards: (u32, u32)) -> usize {
+        if towards.1 > sector.1 {
+            self.up
+        } else if towards.1 < sector.1 {
+            self.down
+        } else if towards.0 < sector.0 {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+/// What happens to the target of a command
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum CommandEffect {
+    /// Removes the given number of sectors from the target program
+    Damage(u32),
+    /// Turns the target tile into a void which cannot be entered
+    DeleteTile,
+    /// Turns the void at the target back into a walkable tile
+    RestoreTile,
+}
+
+impl CommandEffect {
+
+    /// Whether the player can undo the command, which is not the case for damage
+    pub fn can_undo(&self) -> bool {
+        match self {
+            CommandEffect::Damage(_) => false,
+            CommandEffect::DeleteTile | CommandEffect::RestoreTile => true,
+        }
+    }
+}
+
+/// A command a program can execute once per turn
+#[derive(Debug, Clone, Deserialize)]
+pub struct Command {
+    pub name: String,
+    /// Maximal distance (in tiles) between the head of the program and the target
+    pub range: u32,
+    pub effect: CommandEffect,
+}
+
+/// Definition of a program, e.g. HACK or SLINGSHOT
+#[derive(Debug, Clone, Deserialize)]
+pub struct Program {
+    pub name: String,
+    /// Sprite of the head sector, one of the `spriteIds` constants
+    #[serde(deserialize_with = "deserialize_sprite")]
+    pub head: usize,
+    pub tail: TailSprites,
+    /// Number of tiles the program can move per turn
+    pub speed: u32,
+    /// Maximal number of sectors, including the head
+    pub max_size: usize,
+    pub commands: Vec<Command>,
+}
+
+/// Resource holding every program definition of the game
+#[derive(Debug, Clone, Default)]
+pub struct ProgramCatalog {
+    programs: Vec<Program>,
+}
+
+impl ProgramCatalog {
+
+    /// Loads the catalog from a RON file containing a list of programs
+    pub fn load(path: &Path) -> amethyst::Result<Self> {
+        let file = File::open(path)?;
+        let programs: Vec<Program> = ron::de::from_reader(file)?;
+        Ok(ProgramCatalog {
+            programs
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Program> {
+        self.programs.iter().find(|program| program.name == name)
+    }
+
+    pub fn programs(&self) -> &[Program] {
+        &self.programs
+    }
+}
+
+/// Builds a program for tests, with the sprites of HACK and the commands given in RON
+#[cfg(test)]
+pub fn test_program(speed: u32, max_size: usize, commands: &str) -> Program {
+    ron::de::from_str(&format!(
+        "(name: \"TEST\", head: \"HACK\", tail: \"HACK\", speed: {}, max_size: {}, commands: [{}])",
+        speed, max_size, commands,
+    )).unwrap()
+}
@@ -0,0 +1,292 @@
+//! Recording battles and playing them back
+//!
+//! While a battle is fought, the `Replay` resource collects the seed, the uploaded programs and
+//! every action the `ActionSystem` applied, turn by turn. Undone actions are dropped again. Once
+//! the battle is over, the replay is written to the replay directory. Started with
+//! `--replay <file>`, the game builds the field of the recorded level and the `ReplayState`
+//! applies the recorded actions.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use amethyst::{
+    core::Time,
+    ecs::{Entity, Join, World, WorldExt},
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    winit::VirtualKeyCode,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::action::{Action, ActionQueue};
+use crate::body::{ProgramBody, Side};
+use crate::objective::BattleOutcome;
+use crate::save;
+use crate::text::{create_label, delete_labels};
+use crate::turn::{begin_turn, end_turn, Phase, TurnState};
+
+/// Version of the replay format written by this build
+const REPLAY_VERSION: u32 = 2;
+
+/// Seconds between two played back actions at normal speed
+const ACTION_INTERVAL: f32 = 0.5;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+/// The actions of one side in one turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayTurn {
+    pub side: Side,
+    pub actions: Vec<Action>,
+}
+
+/// Resource recording the battle, and the file format of replays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    /// File name of the level in `resource/levels/`
+    pub level: String,
+    /// Seed of the random numbers of the battle
+    pub seed: u64,
+    /// Programs the player uploaded, with the grid position of their heads
+    pub uploads: Vec<(String, (u32, u32))>,
+    pub turns: Vec<ReplayTurn>,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay::new("")
+    }
+}
+
+impl Replay {
+
+    pub fn new(level: &str) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            level: level.to_string(),
+            seed: 0,
+            uploads: Vec::new(),
+            turns: Vec::new(),
+        }
+    }
+
+    pub fn begin_turn(&mut self, side: Side) {
+        self.turns.push(ReplayTurn {
+            side,
+            actions: Vec::new(),
+        });
+    }
+
+    /// Records an applied action in the current turn
+    pub fn record(&mut self, action: Action) {
+        match self.turns.last_mut() {
+            Some(turn) => turn.actions.push(action),
+            None => warn!("Action {:?} outside of a turn is not recorded", action),
+        }
+    }
+
+    /// The actions recorded in the current turn so far
+    pub fn turn_actions(&self) -> Vec<Action> {
+        self.turns.last().map_or_else(Vec::new, |turn| turn.actions.clone())
+    }
+
+    /// Replaces the actions of the current turn, when actions got undone or redone
+    pub fn set_turn_actions(&mut self, actions: Vec<Action>) {
+        if let Some(turn) = self.turns.last_mut() {
+            turn.actions = actions;
+        }
+    }
+
+    pub fn load(path: &Path) -> amethyst::Result<Option<Self>> {
+        save::load(path, REPLAY_VERSION)
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        save::save_compact(self, path)
+    }
+}
+
+/// Resource with the directory replays are written to
+#[derive(Debug, Clone, Default)]
+pub struct ReplayDir {
+    pub path: PathBuf,
+}
+
+/// Records the programs the player uploaded when the battle starts
+pub fn record_uploads(world: &World) {
+    let uploads = (&world.read_storage::<ProgramBody>())
+        .join()
+        .filter(|body| body.side == Side::Player)
+        .map(|body| (body.program.name.clone(), body.head()))
+        .collect();
+    world.write_resource::<Replay>().uploads = uploads;
+}
+
+/// Writes the replay of the finished battle, named after the level and the time it ended
+pub fn save_replay(world: &World) {
+    let replay = world.read_resource::<Replay>();
+    let level = Path::new(&replay.level)
+        .file_stem()
+        .map_or_else(|| "battle".to_string(), |stem| stem.to_string_lossy().into_owned());
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = world.read_resource::<ReplayDir>().path.join(format!("{}-{}.ron", level, seconds));
+    match replay.save(&path) {
+        Ok(()) => info!("Saved the replay to {}", path.display()),
+        Err(error) => warn!("Failed to save the replay to {}: {}", path.display(), error),
+    }
+}
+
+/// Plays back the turns of a replay on the field of its level.
+///
+/// Space plays or pauses, the period key applies the next action, plus and minus change the speed
+/// and escape ends the replay, returning to the campaign map.
+pub struct ReplayState {
+    turns: Vec<ReplayTurn>,
+    /// Index of the turn being played back
+    turn: usize,
+    /// Index of the next action of the turn
+    action: usize,
+    playing: bool,
+    speed: f32,
+    /// Seconds until the next action is played
+    cooldown: f32,
+    labels: Vec<Entity>,
+}
+
+impl ReplayState {
+
+    pub fn new(replay: &Replay) -> Self {
+        ReplayState {
+            turns: replay.turns.clone(),
+            turn: 0,
+            action: 0,
+            playing: true,
+            speed: 1.0,
+            cooldown: ACTION_INTERVAL,
+            labels: Vec::new(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.turn >= self.turns.len()
+    }
+
+    /// Restores the moves of the side of the current turn, like the turn states do
+    fn begin_replay_turn(&self, world: &mut World) {
+        let side = match self.turns.get(self.turn) {
+            Some(turn) => turn.side,
+            None => return,
+        };
+        {
+            let mut turn_state = world.write_resource::<TurnState>();
+            turn_state.phase = match side {
+                Side::Player => Phase::PlayerTurn,
+                Side::Enemy => Phase::EnemyTurn,
+            };
+            if side == Side::Player {
+                turn_state.turn += 1;
+            }
+        }
+        begin_turn(world, side);
+    }
+
+    /// Queues the next recorded action, moving on to the next turn once a turn is played back
+    fn step(&mut self, world: &mut World) {
+        while let Some(turn) = self.turns.get(self.turn) {
+            if let Some(action) = turn.actions.get(self.action) {
+                world.write_resource::<ActionQueue>().push(*action);
+                self.action += 1;
+                return;
+            }
+            end_turn(world, turn.side);
+            self.turn += 1;
+            self.action = 0;
+            self.begin_replay_turn(world);
+        }
+        self.playing = false;
+    }
+
+    fn show_status(&mut self, world: &mut World) {
+        delete_labels(world, &mut self.labels);
+        let status = match self.turns.get(self.turn) {
+            Some(turn) => format!(
+                "Turn {}, {:?}, action {} of {} - {} at {}x",
+                world.read_resource::<TurnState>().turn,
+                turn.side,
+                self.action,
+                turn.actions.len(),
+                if self.playing { "playing" } else { "paused" },
+                self.speed,
+            ),
+            None => match world.read_resource::<BattleOutcome>().result {
+                Some(result) => format!("Replay finished: {:?}", result),
+                None => "Replay finished".to_string(),
+            },
+        };
+        let hint = "Space plays or pauses, period steps, plus and minus change the speed, escape ends the replay".to_string();
+        self.labels.push(create_label(world, "replay_status", status, 300.0, 20.0));
+        self.labels.push(create_label(world, "replay_hint", hint, 270.0, 16.0));
+    }
+}
+
+impl SimpleState for ReplayState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        data.world.write_resource::<TurnState>().replaying = true;
+        info!("Playing back {} turns", self.turns.len());
+        self.begin_replay_turn(data.world);
+        self.show_status(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        data.world.write_resource::<TurnState>().replaying = false;
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                info!("Ending the replay");
+                return Trans::Pop;
+            }
+            let (playing, speed, turn, action) = (self.playing, self.speed, self.turn, self.action);
+            if is_key_down(&event, VirtualKeyCode::Space) && !self.is_finished() {
+                self.playing = !self.playing;
+            }
+            if is_key_down(&event, VirtualKeyCode::Period) && data.world.read_resource::<ActionQueue>().is_empty() {
+                self.playing = false;
+                self.step(data.world);
+            }
+            if is_key_down(&event, VirtualKeyCode::Add) || is_key_down(&event, VirtualKeyCode::Equals) {
+                self.speed = (self.speed * 2.0).min(MAX_SPEED);
+            }
+            if is_key_down(&event, VirtualKeyCode::Subtract) || is_key_down(&event, VirtualKeyCode::Minus) {
+                self.speed = (self.speed * 0.5).max(MIN_SPEED);
+            }
+            if (playing, speed, turn, action) != (self.playing, self.speed, self.turn, self.action) {
+                self.show_status(data.world);
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if !self.playing || !data.world.read_resource::<ActionQueue>().is_empty() {
+            return Trans::None;
+        }
+        self.cooldown -= data.world.read_resource::<Time>().delta_seconds();
+        if self.cooldown <= 0.0 {
+            self.cooldown = ACTION_INTERVAL / self.speed;
+            self.step(data.world);
+            self.show_status(data.world);
+        }
+        Trans::None
+    }
+}
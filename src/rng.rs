@@ -0,0 +1,116 @@
+//! The single source of randomness of the game
+//!
+//! Everything random draws from the `GameRng` resource. Every battle seeds it anew, from the
+//! `--seed <number>` argument, the `seed` of the level or the clock, in that order, and logs the
+//! seed. A battle save keeps the state of the generator and a replay keeps the seed, so both
+//! continue with the same random numbers.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// Seed and position of the `GameRng`, to continue it later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: u64,
+    /// Number of 32 bit words generated since seeding
+    pub word_pos: u64,
+}
+
+/// Resource generating all random numbers of the game
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        GameRng::new(0)
+    }
+}
+
+impl GameRng {
+
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        // Generates the first block, `get_word_pos` of a generator which has not generated any
+        // block yet overflows
+        rng.set_word_pos(0);
+        GameRng {
+            seed,
+            rng,
+        }
+    }
+
+    pub fn state(&self) -> RngState {
+        RngState {
+            seed: self.seed,
+            word_pos: self.rng.get_word_pos() as u64,
+        }
+    }
+
+    /// Continues the generator from a saved state
+    pub fn restore(state: RngState) -> Self {
+        let mut game_rng = GameRng::new(state.seed);
+        game_rng.rng.set_word_pos(state.word_pos as u128);
+        game_rng
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Resource with the seed given on the command line, which replaces the seeds of the levels
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeedOverride {
+    pub seed: Option<u64>,
+}
+
+impl SeedOverride {
+
+    /// The seed of a new battle on a level with the given seed
+    pub fn battle_seed(&self, level_seed: Option<u64>) -> u64 {
+        self.seed.or(level_seed).unwrap_or_else(seed_from_clock)
+    }
+}
+
+/// A seed for battles which have none given
+pub fn seed_from_clock() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restored_generator_continues_where_it_was_saved() {
+        let mut game_rng = GameRng::new(3);
+        let fresh = GameRng::restore(game_rng.state());
+        game_rng.next_u64();
+        let mut restored = GameRng::restore(game_rng.state());
+        assert_eq!(restored.next_u32(), game_rng.next_u32());
+        assert_eq!(fresh.state(), GameRng::new(3).state());
+        assert_eq!(GameRng::new(3).next_u64(), ChaCha8Rng::seed_from_u64(3).next_u64());
+    }
+}
@@ -0,0 +1,54 @@
+//! Versioned RON files in the save directory
+//!
+//! Every save file starts with the version of its format. The version is read on its own first,
+//! so a file of another version is reported as such instead of failing somewhere in the middle.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Only the version of a save file
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+/// Loads a save file of the given version, or returns `None` if the file does not exist
+pub fn load<T: DeserializeOwned>(path: &Path, version: u32) -> amethyst::Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(path)?;
+    let file_version: SaveVersion = ron::de::from_str(&text)?;
+    if file_version.version != version {
+        return Err(amethyst::Error::from_string(format!(
+            "{} has version {}, this build reads version {}",
+            path.display(), file_version.version, version,
+        )));
+    }
+    Ok(Some(ron::de::from_str(&text)?))
+}
+
+/// Saves the value, replacing the previous file only once the new one is written
+pub fn save<T: Serialize>(value: &T, path: &Path) -> amethyst::Result<()> {
+    write(&ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?, path)
+}
+
+/// Saves the value without any whitespace, for files which are not meant to be read by people
+pub fn save_compact<T: Serialize>(value: &T, path: &Path) -> amethyst::Result<()> {
+    write(&ron::ser::to_string(value)?, path)
+}
+
+fn write(text: &str, path: &Path) -> amethyst::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = path.with_extension("ron.tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
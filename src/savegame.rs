@@ -0,0 +1,375 @@
+//! Saving a battle in progress and resuming it later
+//!
+//! A battle is saved at the start of every turn and whenever the player presses F5 during their
+//! turn. The save holds everything which changed since the level was loaded, so resuming loads the
+//! level again, rebuilds the field from the save and continues the saved turn.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use amethyst::ecs::{Builder, Join, World, WorldExt};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::action::ActionQueue;
+use crate::body::{ProgramBody, Side};
+use crate::pickup::{BattleStats, Pickup};
+use crate::program::ProgramCatalog;
+use crate::replay::Replay;
+use crate::rng::{GameRng, RngState};
+use crate::save;
+use crate::turn::{Phase, TurnState};
+use crate::{GameTilePosition, Walkable};
+
+/// Version of the battle save format written by this build
+const SAVE_VERSION: u32 = 3;
+
+/// A program on the field as it was saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedBody {
+    /// Name of the program in the program catalog
+    pub program: String,
+    pub side: Side,
+    /// Grid positions of the sectors, starting with the head
+    pub sectors: Vec<(u32, u32)>,
+    pub moves_left: u32,
+    pub done: bool,
+}
+
+/// A battle in progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedBattle {
+    pub version: u32,
+    /// File name of the level in `resource/levels/`
+    pub level: String,
+    pub turn: u32,
+    /// The side whose turn it is
+    pub side: Side,
+    /// Grid positions of the tiles which are voids
+    pub voids: Vec<(u32, u32)>,
+    /// Pickups not collected yet
+    pub pickups: Vec<((u32, u32), Pickup)>,
+    pub bodies: Vec<SavedBody>,
+    pub credits_collected: u32,
+    pub data_items_collected: u32,
+    /// The recording of the battle so far
+    pub replay: Replay,
+    /// State of the random numbers, which continue from there
+    pub rng: RngState,
+}
+
+impl SavedBattle {
+
+    /// Takes the battle from the world, `None` outside of the turns of the battle
+    pub fn from_world(world: &World, level: &str) -> Option<Self> {
+        let turn_state = world.read_resource::<TurnState>();
+        let side = match turn_state.phase {
+            Phase::PlayerTurn => Side::Player,
+            Phase::EnemyTurn => Side::Enemy,
+            _ => return None,
+        };
+
+        let tile_positions = world.read_storage::<GameTilePosition>();
+        let voids = (&tile_positions, &world.read_storage::<Walkable>())
+            .join()
+            .filter(|(_, walkable)| !walkable.walkable)
+            .map(|(tile_position, _)| tile_position.grid_position)
+            .collect();
+        let pickups = (&tile_positions, &world.read_storage::<Pickup>())
+            .join()
+            .map(|(tile_position, pickup)| (tile_position.grid_position, *pickup))
+            .collect();
+        let bodies = (&world.read_storage::<ProgramBody>())
+            .join()
+            .map(|body| SavedBody {
+                program: body.program.name.clone(),
+                side: body.side,
+                sectors: body.sectors().iter().copied().collect(),
+                moves_left: body.moves_left(),
+                done: body.is_done(),
+            })
+            .collect();
+        let battle_stats = world.read_resource::<BattleStats>();
+
+        Some(SavedBattle {
+            version: SAVE_VERSION,
+            level: level.to_string(),
+            turn: turn_state.turn,
+            side,
+            voids,
+            pickups,
+            bodies,
+            credits_collected: battle_stats.credits_collected,
+            data_items_collected: battle_stats.data_items_collected,
+            replay: (*world.read_resource::<Replay>()).clone(),
+            rng: world.read_resource::<GameRng>().state(),
+        })
+    }
+
+    pub fn is_void(&self, grid_position: (u32, u32)) -> bool {
+        self.voids.contains(&grid_position)
+    }
+
+    pub fn pickup(&self, grid_position: (u32, u32)) -> Option<Pickup> {
+        self.pickups
+            .iter()
+            .find(|(pickup_position, _)| *pickup_position == grid_position)
+            .map(|(_, pickup)| *pickup)
+    }
+
+    /// Places the saved programs and continues the saved turn, once the field is built from the
+    /// saved tiles
+    pub fn restore(&self, world: &mut World) {
+        world.register::<ProgramBody>();
+
+        let catalog = (*world.read_resource::<ProgramCatalog>()).clone();
+        for body in self.bodies.iter() {
+            let program = match catalog.get(&body.program) {
+                Some(program) => program.clone(),
+                None => {
+                    warn!("Program {} is missing in the program catalog", body.program);
+                    continue;
+                }
+            };
+            if body.sectors.is_empty() {
+                warn!("Saved program {} has no sectors", body.program);
+                continue;
+            }
+            world
+                .create_entity()
+                .with(ProgramBody::restore(program, body.side, &body.sectors, body.moves_left, body.done))
+                .build();
+        }
+
+        *world.write_resource::<BattleStats>() = BattleStats {
+            credits_collected: self.credits_collected,
+            data_items_collected: self.data_items_collected,
+        };
+        world.insert(self.replay.clone());
+        world.insert(GameRng::restore(self.rng));
+        let mut turn_state = world.write_resource::<TurnState>();
+        turn_state.turn = self.turn;
+        turn_state.phase = match self.side {
+            Side::Player => Phase::PlayerTurn,
+            Side::Enemy => Phase::EnemyTurn,
+        };
+    }
+
+    /// Loads the saved battle, or returns `None` if there is none
+    pub fn load(path: &Path) -> amethyst::Result<Option<Self>> {
+        save::load(path, SAVE_VERSION)
+    }
+
+    pub fn save(&self, path: &Path) -> amethyst::Result<()> {
+        save::save(self, path)
+    }
+}
+
+/// Resource with the file the running battle is saved to
+#[derive(Debug, Clone, Default)]
+pub struct BattleSaveFile {
+    pub path: PathBuf,
+    /// File name of the level of the running battle
+    pub level: String,
+}
+
+/// Loads the battle save, `None` if there is none or it can not be loaded, e.g. because it was
+/// written by a build with another save format
+pub fn load_battle_save(world: &World) -> Option<SavedBattle> {
+    let path = world.read_resource::<BattleSaveFile>().path.clone();
+    match SavedBattle::load(&path) {
+        Ok(saved) => saved,
+        Err(error) => {
+            warn!("Ignoring the battle save {}: {}", path.display(), error);
+            None
+        }
+    }
+}
+
+/// Saves the running battle, unless an action is still being applied. Simulated battles are not
+/// saved.
+pub fn save_battle(world: &World) {
+    if world.read_resource::<TurnState>().simulated {
+        return;
+    }
+    if !world.read_resource::<ActionQueue>().is_empty() {
+        info!("Wait for the programs to finish moving before saving");
+        return;
+    }
+    let save_file = (*world.read_resource::<BattleSaveFile>()).clone();
+    let saved = match SavedBattle::from_world(world, &save_file.level) {
+        Some(saved) => saved,
+        None => return,
+    };
+    match saved.save(&save_file.path) {
+        Ok(()) => info!("Saved the battle to {}", save_file.path.display()),
+        Err(error) => warn!("Failed to save the battle to {}: {}", save_file.path.display(), error),
+    }
+}
+
+/// Deletes the save of a battle which has ended
+pub fn delete_battle_save(world: &World) {
+    let path = world.read_resource::<BattleSaveFile>().path.clone();
+    if path.exists() {
+        if let Err(error) = fs::remove_file(&path) {
+            warn!("Failed to delete the battle save {}: {}", path.display(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use amethyst::ecs::{Entity, RunNow};
+    use rand::RngCore;
+
+    use super::*;
+    use crate::action::Action;
+    use crate::grid::GameField;
+    use crate::pickup::{pay_credits, PickupSystem, Wallet};
+
+    /// Builds the 3x3 field of the battle like the `Spybotics` state does from a save
+    fn build_field(world: &mut World, voids: &[(u32, u32)], pickups: &[((u32, u32), Pickup)]) {
+        let mut tiles = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                let mut tile = world
+                    .create_entity()
+                    .with(GameTilePosition {
+                        grid_position: (i, j),
+                        world_position: (0.0, 0.0),
+                        world_extent: (0.0, 0.0),
+                    })
+                    .with(Walkable::new(!voids.contains(&(i, j))));
+                if let Some((_, pickup)) = pickups.iter().find(|(grid_position, _)| *grid_position == (i, j)) {
+                    tile = tile.with(*pickup);
+                }
+                tiles.push(tile.build());
+            }
+        }
+        world.insert(GameField::new((3, 3), tiles));
+    }
+
+    fn battle_world() -> World {
+        let mut world = World::new();
+        world.register::<GameTilePosition>();
+        world.register::<Walkable>();
+        world.register::<Pickup>();
+        world.register::<ProgramBody>();
+        let catalog = ProgramCatalog::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("resource/config/programs.ron")).unwrap();
+        world.insert(catalog);
+        world.insert(TurnState::default());
+        world.insert(BattleStats::default());
+        world.insert(Replay::default());
+        world.insert(GameRng::default());
+        world.insert(Wallet::default());
+        world
+    }
+
+    /// The save as text, to compare saves
+    fn text(saved: &SavedBattle) -> String {
+        ron::ser::to_string(saved).unwrap()
+    }
+
+    #[test]
+    fn restore_continues_the_saved_battle() {
+        let mut world = battle_world();
+        build_field(&mut world, &[(0, 2)], &[((2, 2), Pickup::DataItem)]);
+        let hack = (*world.read_resource::<ProgramCatalog>()).get("HACK").unwrap().clone();
+        world
+            .create_entity()
+            .with(ProgramBody::restore(hack, Side::Enemy, &[(1, 1), (1, 0)], 1, false))
+            .build();
+        {
+            let mut turn_state = world.write_resource::<TurnState>();
+            turn_state.phase = Phase::EnemyTurn;
+            turn_state.turn = 4;
+        }
+        world.write_resource::<BattleStats>().credits_collected = 50;
+        let mut replay = Replay::new("training.ron");
+        replay.begin_turn(Side::Enemy);
+        replay.record(Action::Move { from: (1, 0), to: (1, 1) });
+        world.insert(replay);
+        world.insert(GameRng::new(7));
+        world.write_resource::<GameRng>().next_u64();
+
+        let saved = SavedBattle::from_world(&world, "training.ron").unwrap();
+        let path = std::env::temp_dir().join("spybotics-savegame-test.ron");
+        saved.save(&path).unwrap();
+        let saved = SavedBattle::load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut restored = battle_world();
+        build_field(&mut restored, &saved.voids, &saved.pickups);
+        saved.restore(&mut restored);
+
+        let resaved = SavedBattle::from_world(&restored, "training.ron").unwrap();
+        assert_eq!(text(&resaved), text(&SavedBattle::from_world(&world, "training.ron").unwrap()));
+        assert_eq!(
+            restored.write_resource::<GameRng>().next_u64(),
+            world.write_resource::<GameRng>().next_u64(),
+        );
+    }
+
+    /// Moves the head of the program onto the grid position and lets the `PickupSystem` collect
+    fn step_and_collect(world: &mut World, program: Entity, grid_position: (u32, u32)) {
+        world.write_storage::<ProgramBody>().get_mut(program).unwrap().step(grid_position);
+        let mut pickup_system = PickupSystem::default();
+        pickup_system.setup(world);
+        pickup_system.run_now(world);
+        world.maintain();
+    }
+
+    #[test]
+    fn credits_of_a_resumed_battle_are_paid_once() {
+        let mut world = battle_world();
+        build_field(&mut world, &[], &[((1, 2), Pickup::Credits(200))]);
+        let hack = (*world.read_resource::<ProgramCatalog>()).get("HACK").unwrap().clone();
+        let program = world
+            .create_entity()
+            .with(ProgramBody::restore(hack, Side::Player, &[(1, 1)], 2, false))
+            .build();
+        {
+            let mut turn_state = world.write_resource::<TurnState>();
+            turn_state.phase = Phase::PlayerTurn;
+            turn_state.turn = 1;
+        }
+        let saved = SavedBattle::from_world(&world, "training.ron").unwrap();
+
+        // The credits collected before closing the game are not in the profile
+        step_and_collect(&mut world, program, (1, 2));
+        assert_eq!(world.read_resource::<BattleStats>().credits_collected, 200);
+        assert_eq!(world.read_resource::<Wallet>().credits, 0);
+
+        // The resumed battle has the credits on their tile again and pays them once it ends
+        let mut resumed = battle_world();
+        build_field(&mut resumed, &saved.voids, &saved.pickups);
+        saved.restore(&mut resumed);
+        let program = {
+            let entities = resumed.entities();
+            let bodies = resumed.read_storage::<ProgramBody>();
+            (&entities, &bodies).join().map(|(entity, _)| entity).next().unwrap()
+        };
+        step_and_collect(&mut resumed, program, (1, 2));
+        assert_eq!(resumed.read_resource::<BattleStats>().credits_collected, 200);
+        pay_credits(&resumed);
+        assert_eq!(resumed.read_resource::<Wallet>().credits, 200);
+    }
+
+    #[test]
+    fn saves_of_other_versions_are_ignored() {
+        let path = std::env::temp_dir().join("spybotics-old-savegame-test.ron");
+        fs::write(&path, format!("(version: {})", SAVE_VERSION - 1)).unwrap();
+        let mut world = battle_world();
+        world.insert(BattleSaveFile { path: path.clone(), level: String::new() });
+        assert!(load_battle_save(&world).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_save_outside_of_the_turns() {
+        let world = battle_world();
+        assert!(SavedBattle::from_world(&world, "training.ron").is_none());
+    }
+}
@@ -0,0 +1,181 @@
+//! Selecting programs, moving them and choosing targets by clicking on the game field
+
+use std::collections::HashMap;
+
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadStorage, System, Write};
+
+use log::info;
+
+use crate::action::{occupied_cells, Action, ActionQueue};
+use crate::body::{ProgramBody, Side};
+use crate::command::validate;
+use crate::grid::{path_to, reachable, within_range, GameField};
+use crate::history::{Snapshot, TurnHistory};
+use crate::overlay::{Highlight, Overlay};
+use crate::pickup::Pickup;
+use crate::program::CommandEffect;
+use crate::replay::Replay;
+use crate::turn::{Phase, TurnState};
+use crate::{spriteIds, TileClick, Walkable};
+
+/// Resource holding the program the player currently controls
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub program: Option<Entity>,
+    /// Index of the chosen command of the selected program, if the player is choosing a target
+    pub command: Option<usize>,
+}
+
+impl Selection {
+
+    /// Whether the chosen command of the selected program targets voids
+    pub fn targets_voids(&self, bodies: &ReadStorage<'_, ProgramBody>) -> bool {
+        let body = self.program.and_then(|program| bodies.get(program));
+        match (body, self.command) {
+            (Some(body), Some(command)) => body.program.commands
+                .get(command)
+                .map_or(false, |command| matches!(command.effect, CommandEffect::RestoreTile)),
+            _ => false,
+        }
+    }
+}
+
+/// Handles clicks on the game field during the player turn.
+///
+/// Without a chosen command, the tiles the selected program can reach this turn are highlighted
+/// and clicking one of them queues the moves to get there. With a chosen command, the tiles in
+/// its range are highlighted, and clicking a valid target queues the command.
+#[derive(Default)]
+pub struct SelectionSystem;
+
+impl<'a> System<'a> for SelectionSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, TurnState>,
+        Read<'a, GameField>,
+        Read<'a, Replay>,
+        Write<'a, TileClick>,
+        Write<'a, Selection>,
+        Write<'a, ActionQueue>,
+        Write<'a, Overlay>,
+        Write<'a, TurnHistory>,
+        ReadStorage<'a, ProgramBody>,
+        ReadStorage<'a, Walkable>,
+        ReadStorage<'a, Pickup>,
+    );
+
+    fn run(&mut self, ( entities,
+                        turn_state,
+                        game_field,
+                        replay,
+                        mut tile_click,
+                        mut selection,
+                        mut action_queue,
+                        mut overlay,
+                        mut turn_history,
+                        bodies,
+                        walkable,
+                        pickups,
+                        ): Self::SystemData) {
+
+        let clicked = tile_click.grid_position.take();
+
+        if turn_state.phase != Phase::PlayerTurn || turn_state.replaying {
+            selection.program = None;
+            selection.command = None;
+            overlay.clear();
+            return;
+        }
+
+        // Programs which got deleted or have finished their turn cannot be controlled anymore
+        if let Some(selected) = selection.program {
+            if bodies.get(selected).map_or(true, |body| body.is_done()) {
+                selection.program = None;
+            }
+        }
+        let selected = selection.program.and_then(|selected| bodies.get(selected).map(|body| (selected, body)));
+        let command_available = match (selected, selection.command) {
+            (Some((_, body)), Some(command)) => command < body.program.commands.len(),
+            _ => false,
+        };
+        if !command_available {
+            selection.command = None;
+        }
+
+        let mut highlights = Vec::new();
+        let mut reached = HashMap::new();
+        let mut targets = Vec::new();
+
+        if let (Some((selected, body)), true) = (selected, action_queue.is_empty()) {
+            match selection.command {
+                Some(command) => {
+                    let range = body.program.commands[command].range;
+                    for grid_position in within_range(body.head(), range, game_field.extent()) {
+                        let target_body = (&bodies).join().find(|target| target.occupies(grid_position));
+                        let target_walkable = game_field
+                            .tile(grid_position)
+                            .and_then(|tile| walkable.get(tile))
+                            .map(|tile| tile.walkable);
+                        let target_pickup = game_field.tile(grid_position).map_or(false, |tile| pickups.contains(tile));
+                        if validate(body, command, grid_position, target_body, target_walkable, target_pickup).is_ok() {
+                            targets.push(grid_position);
+                            highlights.push(Highlight::new(grid_position, spriteIds::SELECTSQUARERED));
+                        } else {
+                            highlights.push(Highlight::dimmed(grid_position, spriteIds::SELECTSQUARERED));
+                        }
+                    }
+                }
+                None => {
+                    let occupied = occupied_cells((&entities, &bodies).join(), Some(selected));
+                    reached = reachable(body.head(), body.moves_left(), game_field.extent(), |grid_position| {
+                        !occupied.contains(&grid_position) && game_field
+                            .tile(grid_position)
+                            .and_then(|tile| walkable.get(tile))
+                            .map_or(false, |tile| tile.walkable)
+                    });
+                    highlights.extend(reached
+                        .iter()
+                        .filter(|(grid_position, previous)| grid_position != previous)
+                        .map(|(grid_position, _)| Highlight::new(*grid_position, spriteIds::SELECTSQUAREGREEN)));
+                }
+            }
+        }
+
+        if let (Some(grid_position), true) = (clicked, action_queue.is_empty()) {
+            let head = selected.map(|(_, body)| body.head());
+
+            if let (Some(command), Some(head), true) = (selection.command, head, targets.contains(&grid_position)) {
+                turn_history.record(Snapshot::take(&entities, &bodies, &walkable, &replay));
+                action_queue.push(Action::Command { from: head, command, target: grid_position });
+                selection.command = None;
+            } else if let Some(path) = path_to(&reached, grid_position).filter(|path| path.len() > 1) {
+                turn_history.record(Snapshot::take(&entities, &bodies, &walkable, &replay));
+                for step in path.windows(2) {
+                    action_queue.push(Action::Move { from: step[0], to: step[1] });
+                }
+            } else {
+                selection.command = None;
+                selection.program = (&entities, &bodies)
+                    .join()
+                    .find(|(_, body)| body.side == Side::Player && !body.is_done() && body.occupies(grid_position))
+                    .map(|(entity, _)| entity);
+
+                if let Some(body) = selection.program.and_then(|selected| bodies.get(selected)) {
+                    let commands: Vec<String> = body.program.commands
+                        .iter()
+                        .enumerate()
+                        .map(|(index, command)| format!("[{}] {} (range {})", index + 1, command.name, command.range))
+                        .collect();
+                    info!("Selected {}, {} move(s) left, commands: {}", body.program.name, body.moves_left(), commands.join(", "));
+                }
+            }
+        }
+
+        if action_queue.is_empty() && clicked.is_none() {
+            overlay.set(highlights);
+        } else {
+            overlay.clear();
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! The Warez shop, selling programs for the credits earned in battle
+
+use std::fs::File;
+use std::path::Path;
+
+use amethyst::{
+    ecs::{Entity, World, WorldExt},
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    renderer::SpriteRender,
+    winit::VirtualKeyCode,
+};
+
+use log::info;
+use serde::Deserialize;
+
+use crate::pickup::Wallet;
+use crate::profile::save_profile;
+use crate::program::ProgramCatalog;
+use crate::sprite_sheet;
+use crate::text::{create_icon, create_label, delete_labels};
+use crate::turn::NUMBER_KEYS;
+use crate::upload::OwnedPrograms;
+
+/// A program for sale
+#[derive(Debug, Clone, Deserialize)]
+pub struct Offer {
+    /// Name of the program in the program catalog
+    pub program: String,
+    /// Credits a copy costs
+    pub price: u32,
+}
+
+/// Resource listing the programs for sale
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Shop {
+    pub offers: Vec<Offer>,
+}
+
+impl Shop {
+
+    /// Loads the offers from a RON file
+    pub fn load(path: &Path) -> amethyst::Result<Self> {
+        let file = File::open(path)?;
+        Ok(ron::de::from_reader(file)?)
+    }
+}
+
+/// Buys a copy of the offered program if the player has enough credits
+fn buy(world: &mut World, offer: &Offer) {
+    let mut wallet = world.write_resource::<Wallet>();
+    if wallet.credits < offer.price {
+        info!("{} costs {} credits, you have {}", offer.program, offer.price, wallet.credits);
+        return;
+    }
+    wallet.credits -= offer.price;
+    world.write_resource::<OwnedPrograms>().add(&offer.program);
+    info!("Bought {} for {} credits, {} credits left", offer.program, offer.price, wallet.credits);
+}
+
+/// Lists the offers of the `Shop`, the number keys buy a copy of the program
+#[derive(Default)]
+pub struct ShopState {
+    labels: Vec<Entity>,
+}
+
+impl ShopState {
+
+    fn show_offers(&mut self, world: &mut World) {
+        let sprite_sheet = sprite_sheet(world);
+        let offers = world.read_resource::<Shop>().offers.clone();
+        let credits = world.read_resource::<Wallet>().credits;
+
+        self.labels.push(create_label(world, "shop_title", "Warez".to_string(), 200.0, 40.0));
+        self.labels.push(create_label(world, "shop_credits", format!("{} credits", credits), 150.0, 24.0));
+
+        for (index, offer) in offers.iter().enumerate() {
+            let y = 90.0 - 40.0 * index as f32;
+            let head = world.read_resource::<ProgramCatalog>().get(&offer.program).map(|program| program.head);
+            if let Some(head) = head {
+                let sprite = SpriteRender {
+                    sprite_sheet: sprite_sheet.clone(),
+                    sprite_number: head,
+                };
+                let id = format!("shop_icon_{}", index);
+                self.labels.push(create_icon(world, &id, sprite, -240.0, y, 32.0));
+            }
+            let owned = world.read_resource::<OwnedPrograms>().copies(&offer.program);
+            let text = format!("[{}] {} - {} credits (owned: {})", index + 1, offer.program, offer.price, owned);
+            let id = format!("shop_offer_{}", index);
+            self.labels.push(create_label(world, &id, text, y, 24.0));
+        }
+
+        let hint = "Buy a program with the number keys, escape to return to the map".to_string();
+        self.labels.push(create_label(world, "shop_hint", hint, -240.0, 18.0));
+    }
+}
+
+impl SimpleState for ShopState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.show_offers(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            }
+            if let Some(index) = NUMBER_KEYS.iter().position(|key| is_key_down(&event, *key)) {
+                let offer = data.world.read_resource::<Shop>().offers.get(index).cloned();
+                if let Some(offer) = offer {
+                    buy(data.world, &offer);
+                    save_profile(data.world);
+                    delete_labels(data.world, &mut self.labels);
+                    self.show_offers(data.world);
+                }
+            }
+        }
+        Trans::None
+    }
+}
@@ -0,0 +1,185 @@
+//! Battles fought by the AI on both sides, without a window
+//!
+//! The `simulate` binary plays a level through the states and systems of the game: the
+//! `Spybotics` state sets up the field, the roster is uploaded onto the player upload zones in its
+//! order and the AI takes the turns of both sides until a condition of the level is met. The
+//! action log and the result are printed and the battle is written as a replay, so it can be
+//! watched with `--replay`. Built with the `empty` feature instead of a graphics backend, it needs
+//! no window or GPU:
+//!
+//! `cargo run --no-default-features --features empty --bin simulate -- training.ron --difficulty easy`
+//!
+//! The AI of both sides searches a fixed number of boards instead of searching for a time, so a
+//! seed plays the same battle on every machine, whatever the difficulty.
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use amethyst::{
+    core::frame_limiter::FrameRateLimitStrategy,
+    prelude::*,
+};
+
+use crate::action::Action;
+use crate::ai::Difficulty;
+use crate::objective::{BattleOutcome, BattleResult};
+use crate::pickup::{BattleStats, Wallet};
+use crate::program::ProgramCatalog;
+use crate::replay::{save_replay, Replay, ReplayDir};
+use crate::rng::{GameRng, SeedOverride};
+use crate::savegame::BattleSaveFile;
+use crate::turn::{Phase, TurnState};
+use crate::upload::OwnedPrograms;
+use crate::{app_root, battle_systems, difficulty_from_args, seed_from_args, Spybotics};
+use crate::{ASSET_PATH, LEVELS_PATH, PROGRAMS_PATH, REPLAYS_PATH};
+
+/// How a simulated battle went
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    /// `None` if the battle was called off without a result
+    pub result: Option<BattleResult>,
+    /// The turn the battle ended in
+    pub turn: u32,
+    pub credits_collected: u32,
+    pub data_items_collected: u32,
+    pub replay: Replay,
+}
+
+/// Pushes the battle, and reports it once the battle returns
+struct SimulationState {
+    levels_dir: PathBuf,
+    level_name: String,
+    started: bool,
+    report: Arc<Mutex<Option<SimulationReport>>>,
+}
+
+impl SimpleState for SimulationState {
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if !self.started {
+            self.started = true;
+            return Trans::Push(Box::new(Spybotics::simulate(self.levels_dir.clone(), &self.level_name)));
+        }
+
+        let world = &*data.world;
+        let (phase, turn) = {
+            let turn_state = world.read_resource::<TurnState>();
+            (turn_state.phase, turn_state.turn)
+        };
+        // A level which failed to load returns before the battle started
+        if phase == Phase::Setup {
+            return Trans::Quit;
+        }
+        let result = world.read_resource::<BattleOutcome>().result;
+        // Decided battles wrote their replay when they ended
+        if result.is_none() {
+            save_replay(world);
+        }
+        let battle_stats = world.read_resource::<BattleStats>();
+        *self.report.lock().unwrap() = Some(SimulationReport {
+            result,
+            turn,
+            credits_collected: battle_stats.credits_collected,
+            data_items_collected: battle_stats.data_items_collected,
+            replay: (*world.read_resource::<Replay>()).clone(),
+        });
+        Trans::Quit
+    }
+}
+
+/// Plays the level with the AI on both sides, without a window, and writes the replay to
+/// `replay_dir`
+pub fn simulate(level_name: &str, difficulty: Difficulty, seed_override: SeedOverride, replay_dir: PathBuf) -> amethyst::Result<SimulationReport> {
+    let app_root = app_root();
+    let program_catalog = ProgramCatalog::load(&app_root.join(PROGRAMS_PATH))?;
+    let report = Arc::new(Mutex::new(None));
+    let state = SimulationState {
+        levels_dir: app_root.join(LEVELS_PATH),
+        level_name: level_name.to_string(),
+        started: false,
+        report: Arc::clone(&report),
+    };
+
+    // The simulation uploads the starting programs and neither saves nor earns anything, so its
+    // battles do not depend on the profile
+    let mut game = Application::build(app_root.join(ASSET_PATH), state)?
+        .with_frame_limit(FrameRateLimitStrategy::Unlimited, 0)
+        .with_resource(program_catalog)
+        .with_resource(difficulty)
+        .with_resource(OwnedPrograms::default())
+        .with_resource(Wallet::default())
+        .with_resource(BattleSaveFile::default())
+        .with_resource(ReplayDir { path: replay_dir })
+        .with_resource(seed_override)
+        .with_resource(GameRng::default())
+        .build(battle_systems(GameDataBuilder::default()))?;
+    game.run();
+
+    let report = report.lock().unwrap().take();
+    report.ok_or_else(|| amethyst::Error::from_string(format!("The battle of level {} did not start", level_name)))
+}
+
+/// Describes an action for the action log
+fn describe(action: Action) -> String {
+    match action {
+        Action::Move { from, to } => format!("{:?} moves to {:?}", from, to),
+        Action::Command { from, command, target } => format!("{:?} uses command {} on {:?}", from, command + 1, target),
+    }
+}
+
+/// Simulates the level given as first argument, with the `--difficulty` and `--seed` arguments of
+/// the game
+pub fn main() -> amethyst::Result<()> {
+    amethyst::start_logger(Default::default());
+
+    let level_name = env::args()
+        .nth(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .ok_or_else(|| amethyst::Error::from_string(
+            "Usage: simulate <level> [--difficulty <easy|normal|hard>] [--seed <number>]".to_string(),
+        ))?;
+    let difficulty = difficulty_from_args()?;
+    println!("Simulating level {} on {:?} difficulty", level_name, difficulty);
+
+    let report = simulate(&level_name, difficulty, seed_from_args()?, app_root().join(REPLAYS_PATH))?;
+    println!("Random seed of the battle: {}", report.replay.seed);
+    for (program, grid_position) in report.replay.uploads.iter() {
+        println!("Uploaded {} at {:?}", program, grid_position);
+    }
+    // Every turn has a player and an enemy part
+    for (index, turn) in report.replay.turns.iter().enumerate() {
+        println!("Turn {}: {:?}", index / 2 + 1, turn.side);
+        for action in turn.actions.iter() {
+            println!("  {}", describe(*action));
+        }
+    }
+    match report.result {
+        Some(result) => println!(
+            "{:?} in turn {}, {} credits and {} data items collected",
+            result, report.turn, report.credits_collected, report.data_items_collected,
+        ),
+        None => println!("No result after {} turns", report.turn),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::body::Side;
+
+    #[test]
+    fn training_is_won_on_easy() {
+        let replay_dir = env::temp_dir().join("spybotics-simulation-test");
+        let report = simulate("training.ron", Difficulty::Easy, SeedOverride { seed: Some(1) }, replay_dir.clone()).unwrap();
+        assert_eq!(report.result, Some(BattleResult::Victory));
+
+        assert_eq!(report.replay.seed, 1);
+        assert_eq!(report.replay.uploads.len(), 4);
+        assert!(report.replay.turns.iter().any(|turn| turn.side == Side::Enemy && !turn.actions.is_empty()));
+        assert_eq!(fs::read_dir(&replay_dir).unwrap().count(), 1);
+        fs::remove_dir_all(&replay_dir).unwrap();
+    }
+}
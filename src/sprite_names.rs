@@ -0,0 +1,168 @@
+//! Lookup of sprite ids by the names used in `spriteIds`.
+//!
+//! Data files refer to sprites by name (e.g. `"HACK"`), since the numeric ids in `spriteIds`
+//! change every time TexturePacker repacks the sprite sheet.
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::spriteIds;
+
+/// Returns the id of the sprite with the given `spriteIds` constant name
+pub fn sprite_id(name: &str) -> Option<usize> {
+    let id = match name {
+        "ARROW" => spriteIds::ARROW,
+        "ATTACKDOG" => spriteIds::ATTACKDOG,
+        "BALLISTA" => spriteIds::BALLISTA,
+        "BIT_MAN" => spriteIds::BIT_MAN,
+        "BIT_MANTAILDOWN" => spriteIds::BIT_MANTAILDOWN,
+        "BIT_MANTAILLEFT" => spriteIds::BIT_MANTAILLEFT,
+        "BIT_MANTAILRIGHT" => spriteIds::BIT_MANTAILRIGHT,
+        "BIT_MANTAILUP" => spriteIds::BIT_MANTAILUP,
+        "BLACKWIDOW" => spriteIds::BLACKWIDOW,
+        "BOMBTAILDOWN" => spriteIds::BOMBTAILDOWN,
+        "BOMBTAILLEFT" => spriteIds::BOMBTAILLEFT,
+        "BOMBTAILRIGHT" => spriteIds::BOMBTAILRIGHT,
+        "BOMBTAILUP" => spriteIds::BOMBTAILUP,
+        "BOSS" => spriteIds::BOSS,
+        "BOSSTAILDOWN" => spriteIds::BOSSTAILDOWN,
+        "BOSSTAILLEFT" => spriteIds::BOSSTAILLEFT,
+        "BOSSTAILRIGHT" => spriteIds::BOSSTAILRIGHT,
+        "BOSSTAILUP" => spriteIds::BOSSTAILUP,
+        "BUG" => spriteIds::BUG,
+        "BUGTAILDOWN" => spriteIds::BUGTAILDOWN,
+        "BUGTAILLEFT" => spriteIds::BUGTAILLEFT,
+        "BUGTAILRIGHT" => spriteIds::BUGTAILRIGHT,
+        "BUGTAILUP" => spriteIds::BUGTAILUP,
+        "BUZZBOMB" => spriteIds::BUZZBOMB,
+        "CATAPULT" => spriteIds::CATAPULT,
+        "CHECKMARK" => spriteIds::CHECKMARK,
+        "CLOG_01" => spriteIds::CLOG_01,
+        "CLOG_02" => spriteIds::CLOG_02,
+        "CLOG_03" => spriteIds::CLOG_03,
+        "CLOGTAILDOWN" => spriteIds::CLOGTAILDOWN,
+        "CLOGTAILLEFT" => spriteIds::CLOGTAILLEFT,
+        "CLOGTAILRIGHT" => spriteIds::CLOGTAILRIGHT,
+        "CLOGTAILUP" => spriteIds::CLOGTAILUP,
+        "COIN" => spriteIds::COIN,
+        "COMMANDDOWN" => spriteIds::COMMANDDOWN,
+        "COMMANDLEFT" => spriteIds::COMMANDLEFT,
+        "COMMANDUP" => spriteIds::COMMANDUP,
+        "COMMANRIGHT" => spriteIds::COMMANRIGHT,
+        "CREDITS" => spriteIds::CREDITS,
+        "DATADOCTOR" => spriteIds::DATADOCTOR,
+        "DATADOCTORPRO" => spriteIds::DATADOCTORPRO,
+        "DATAITEM" => spriteIds::DATAITEM,
+        "DOGTAILDOWN" => spriteIds::DOGTAILDOWN,
+        "DOGTAILLEFT" => spriteIds::DOGTAILLEFT,
+        "DOGTAILRIGHT" => spriteIds::DOGTAILRIGHT,
+        "DOGTAILUP" => spriteIds::DOGTAILUP,
+        "FIDDLE" => spriteIds::FIDDLE,
+        "FIREWALL" => spriteIds::FIREWALL,
+        "GOLEM_CLAY" => spriteIds::GOLEM_CLAY,
+        "GOLEM_MUD" => spriteIds::GOLEM_MUD,
+        "GOLEM_STONE" => spriteIds::GOLEM_STONE,
+        "GOLEMTAILDOWN" => spriteIds::GOLEMTAILDOWN,
+        "GOLEMTAILLEFT" => spriteIds::GOLEMTAILLEFT,
+        "GOLEMTAILRIGHT" => spriteIds::GOLEMTAILRIGHT,
+        "GOLEMTAILUP" => spriteIds::GOLEMTAILUP,
+        "GUARDDOG" => spriteIds::GUARDDOG,
+        "GUARDPUP" => spriteIds::GUARDPUP,
+        "GURU" => spriteIds::GURU,
+        "HACK" => spriteIds::HACK,
+        "HACK2_0" => spriteIds::HACK2_0,
+        "HACK3_0" => spriteIds::HACK3_0,
+        "HACKTAILDOWN" => spriteIds::HACKTAILDOWN,
+        "HACKTAILLEFT" => spriteIds::HACKTAILLEFT,
+        "HACKTAILRIGHT" => spriteIds::HACKTAILRIGHT,
+        "HACKTAILUP" => spriteIds::HACKTAILUP,
+        "HEISENBUG" => spriteIds::HEISENBUG,
+        "LASERSATELLITE" => spriteIds::LASERSATELLITE,
+        "LOGICBOMB" => spriteIds::LOGICBOMB,
+        "MANDELBUG" => spriteIds::MANDELBUG,
+        "MANDELBUGEVIL" => spriteIds::MANDELBUGEVIL,
+        "MEDIC" => spriteIds::MEDIC,
+        "MEDICTAILDOWN" => spriteIds::MEDICTAILDOWN,
+        "MEDICTAILLEFT" => spriteIds::MEDICTAILLEFT,
+        "MEDICTAILRIGHT" => spriteIds::MEDICTAILRIGHT,
+        "MEDICTAILUP" => spriteIds::MEDICTAILUP,
+        "MEMORYHOG" => spriteIds::MEMORYHOG,
+        "MOBILETOWER" => spriteIds::MOBILETOWER,
+        "PURPLEMANDELBUG" => spriteIds::PURPLEMANDELBUG,
+        "PURPLEMANDELBUGTAILDOWN" => spriteIds::PURPLEMANDELBUGTAILDOWN,
+        "PURPLEMANDELBUGTAILLEFT" => spriteIds::PURPLEMANDELBUGTAILLEFT,
+        "PURPLEMANDELBUGTAILRIGHT" => spriteIds::PURPLEMANDELBUGTAILRIGHT,
+        "PURPLEMANDELBUGTAILUP" => spriteIds::PURPLEMANDELBUGTAILUP,
+        "PURPLESUMO" => spriteIds::PURPLESUMO,
+        "PURPLESUMOTAILDOWN" => spriteIds::PURPLESUMOTAILDOWN,
+        "PURPLESUMOTAILLEFT" => spriteIds::PURPLESUMOTAILLEFT,
+        "PURPLESUMOTAILRIGHT" => spriteIds::PURPLESUMOTAILRIGHT,
+        "PURPLESUMOTAILUP" => spriteIds::PURPLESUMOTAILUP,
+        "RADAR" => spriteIds::RADAR,
+        "REPAIRGRIDSQUARE" => spriteIds::REPAIRGRIDSQUARE,
+        "SATELLITE" => spriteIds::SATELLITE,
+        "SEEKER" => spriteIds::SEEKER,
+        "SEEKER2_0" => spriteIds::SEEKER2_0,
+        "SEEKER3_0" => spriteIds::SEEKER3_0,
+        "SELECTSQUAREGREEN" => spriteIds::SELECTSQUAREGREEN,
+        "SELECTSQUARERED" => spriteIds::SELECTSQUARERED,
+        "SENSOR" => spriteIds::SENSOR,
+        "SENTINAL" => spriteIds::SENTINAL,
+        "SENTINAL2_0" => spriteIds::SENTINAL2_0,
+        "SENTINAL3_0" => spriteIds::SENTINAL3_0,
+        "SENTINELTAILDOWN" => spriteIds::SENTINELTAILDOWN,
+        "SENTINELTAILLEFT" => spriteIds::SENTINELTAILLEFT,
+        "SENTINELTAILRIGHT" => spriteIds::SENTINELTAILRIGHT,
+        "SENTINELTAILUP" => spriteIds::SENTINELTAILUP,
+        "SLINGSHOT" => spriteIds::SLINGSHOT,
+        "SLINGSHOTTAILDOWN" => spriteIds::SLINGSHOTTAILDOWN,
+        "SLINGSHOTTAILLEFT" => spriteIds::SLINGSHOTTAILLEFT,
+        "SLINGSHOTTAILRIGHT" => spriteIds::SLINGSHOTTAILRIGHT,
+        "SLINGSHOTTAILUP" => spriteIds::SLINGSHOTTAILUP,
+        "SONAR" => spriteIds::SONAR,
+        "SPIDERTAILDOWN" => spriteIds::SPIDERTAILDOWN,
+        "SPIDERTAILLEFT" => spriteIds::SPIDERTAILLEFT,
+        "SPIDERTAILRIGHT" => spriteIds::SPIDERTAILRIGHT,
+        "SPIDERTAILUP" => spriteIds::SPIDERTAILUP,
+        "STACK" => spriteIds::STACK,
+        "SUMO" => spriteIds::SUMO,
+        "SUMOEVIL" => spriteIds::SUMOEVIL,
+        "TARANTULA" => spriteIds::TARANTULA,
+        "TOWER" => spriteIds::TOWER,
+        "TOWERTAILDOWN" => spriteIds::TOWERTAILDOWN,
+        "TOWERTAILLEFT" => spriteIds::TOWERTAILLEFT,
+        "TOWERTAILRIGHT" => spriteIds::TOWERTAILRIGHT,
+        "TOWERTAILUP" => spriteIds::TOWERTAILUP,
+        "TURBO" => spriteIds::TURBO,
+        "TURBODELUXE" => spriteIds::TURBODELUXE,
+        "UPLOADZONE" => spriteIds::UPLOADZONE,
+        "UPLOADZONE1" => spriteIds::UPLOADZONE1,
+        "UPLOADZONE2" => spriteIds::UPLOADZONE2,
+        "UPLOADZONE3" => spriteIds::UPLOADZONE3,
+        "WARDEN_PP" => spriteIds::WARDEN_PP,
+        "WARDEN_P" => spriteIds::WARDEN_P,
+        "WARDEN" => spriteIds::WARDEN,
+        "WARDENTAILDOWN" => spriteIds::WARDENTAILDOWN,
+        "WARDENTAILLEFT" => spriteIds::WARDENTAILLEFT,
+        "WARDENTAILRIGHT" => spriteIds::WARDENTAILRIGHT,
+        "WARDENTAILUP" => spriteIds::WARDENTAILUP,
+        "WATCHMAN" => spriteIds::WATCHMAN,
+        "WATCHMANSP" => spriteIds::WATCHMANSP,
+        "WATCHMANTAILDOWN" => spriteIds::WATCHMANTAILDOWN,
+        "WATCHMANTAILLEFT" => spriteIds::WATCHMANTAILLEFT,
+        "WATCHMANTAILRIGHT" => spriteIds::WATCHMANTAILRIGHT,
+        "WATCHMANTAILUP" => spriteIds::WATCHMANTAILUP,
+        "WATCHMANX" => spriteIds::WATCHMANX,
+        "WIZARD" => spriteIds::WIZARD,
+        "WOLFSPIDER" => spriteIds::WOLFSPIDER,
+        _ => return None,
+    };
+    Some(id)
+}
+
+/// Deserializes a sprite name into its sprite id, for use with `#[serde(deserialize_with)]`
+pub fn deserialize_sprite<'de, D>(deserializer: D) -> Result<usize, D::Error>
+    where D: Deserializer<'de>
+{
+    let name = String::deserialize(deserializer)?;
+    sprite_id(&name).ok_or_else(|| de::Error::custom(format!("unknown sprite `{}`", name)))
+}
@@ -0,0 +1,66 @@
+//! Text labels and icons on the screen
+
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    ecs::{Entity, World, WorldExt},
+    prelude::*,
+    renderer::SpriteRender,
+    ui::{get_default_font, Anchor, FontAsset, UiImage, UiText, UiTransform},
+};
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Creates a label centered horizontally, `y` pixels above the middle of the screen
+pub fn create_label(world: &mut World, id: &str, text: String, y: f32, font_size: f32) -> Entity {
+    let font = {
+        let loader = world.read_resource::<Loader>();
+        let fonts = world.read_resource::<AssetStorage<FontAsset>>();
+        get_default_font(&loader, &fonts)
+    };
+
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::Middle,
+        Anchor::Middle,
+        0.0,
+        y,
+        1.0,
+        1200.0,
+        font_size * 1.5,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(font, text, TEXT_COLOR, font_size))
+        .build()
+}
+
+/// Creates a square icon showing the sprite, centered at `x` and `y` pixels from the middle of
+/// the screen
+pub fn create_icon(world: &mut World, id: &str, sprite: SpriteRender, x: f32, y: f32, size: f32) -> Entity {
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::Middle,
+        Anchor::Middle,
+        x,
+        y,
+        1.0,
+        size,
+        size,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiImage::Sprite(sprite))
+        .build()
+}
+
+/// Deletes the given labels
+pub fn delete_labels(world: &mut World, labels: &mut Vec<Entity>) {
+    world
+        .delete_entities(labels)
+        .expect("Failed to delete labels.");
+    labels.clear();
+}
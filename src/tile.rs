@@ -0,0 +1,118 @@
+//! Sprites of the game tiles themselves
+
+use std::collections::HashMap;
+
+use amethyst::{
+    core::{Time, Transform},
+    ecs::{Entities, Entity, Join, Read, ReadStorage, System, WriteStorage},
+    renderer::SpriteRender,
+};
+
+use crate::grid::{tile_sprite_transform, HIGHLIGHT_LAYER, TILE_LAYER};
+use crate::{spriteIds, GameTilePosition, GameTileSpriteStack, HandleHandle, Walkable};
+
+/// Sprite of a walkable tile. The sprite sheet has no dedicated floor sprite, the plain upload
+/// zone doubles as one.
+pub const FLOOR_SPRITE: usize = spriteIds::UPLOADZONE;
+
+/// Seconds the repair square is shown on a restored tile
+const REPAIR_FEEDBACK_SECONDS: f32 = 0.5;
+
+/// Removes the floor sprite of tiles which became voids and restores it when they become walkable
+/// again, showing `REPAIRGRIDSQUARE` for a moment.
+#[derive(Default)]
+pub struct TileSpriteSystem {
+    /// Floor sprite of every tile, `None` for voids
+    floor_sprites: HashMap<Entity, Option<Entity>>,
+    /// Repair squares on restored tiles with the seconds they are still shown
+    repair_sprites: Vec<(Entity, Entity, f32)>,
+}
+
+impl<'a> System<'a> for TileSpriteSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Time>,
+        Read<'a, HandleHandle>,
+        ReadStorage<'a, GameTilePosition>,
+        ReadStorage<'a, Walkable>,
+        WriteStorage<'a, GameTileSpriteStack>,
+        WriteStorage<'a, SpriteRender>,
+        WriteStorage<'a, Transform>,
+    );
+
+    fn run(&mut self, ( entities,
+                        time,
+                        sprite_sheet_handle,
+                        game_tile_position,
+                        walkable,
+                        mut game_tile_sprite_stack,
+                        mut sprites,
+                        mut transforms,
+                        ): Self::SystemData) {
+
+        let mut repair_sprites = Vec::new();
+        for (tile, sprite, seconds_left) in self.repair_sprites.drain(..) {
+            let seconds_left = seconds_left - time.delta_seconds();
+            if seconds_left > 0.0 {
+                repair_sprites.push((tile, sprite, seconds_left));
+                continue;
+            }
+            if let Some(tile_stack) = game_tile_sprite_stack.get_mut(tile) {
+                tile_stack.sprite_stack.retain(|stacked| *stacked != sprite);
+            }
+            if entities.is_alive(sprite) {
+                entities.delete(sprite).expect("Failed to delete repair sprite.");
+            }
+        }
+        self.repair_sprites = repair_sprites;
+
+        // Forget the tiles of fields which were torn down
+        self.floor_sprites.retain(|tile, _| entities.is_alive(*tile));
+
+        let sprite_sheet = match sprite_sheet_handle.sprite_sheet_handle.as_ref() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        for (tile, tile_position, tile_walkable, tile_stack) in (&entities, &game_tile_position, &walkable, &mut game_tile_sprite_stack).join() {
+            // The floor sprite is the bottom of the stack of the tiles created by `initialize_field`
+            let floor_sprite = self.floor_sprites.entry(tile).or_insert_with(|| match tile_walkable.walkable {
+                true => tile_stack.sprite_stack.first().copied(),
+                false => None,
+            });
+
+            match (tile_walkable.walkable, *floor_sprite) {
+                (false, Some(sprite)) => {
+                    tile_stack.sprite_stack.retain(|stacked| *stacked != sprite);
+                    entities.delete(sprite).expect("Failed to delete floor sprite.");
+                    *floor_sprite = None;
+                }
+                (true, None) => {
+                    let sprite = entities
+                        .build_entity()
+                        .with(SpriteRender {
+                            sprite_sheet: sprite_sheet.clone(),
+                            sprite_number: FLOOR_SPRITE,
+                        }, &mut sprites)
+                        .with(tile_sprite_transform(tile_position, TILE_LAYER), &mut transforms)
+                        .build();
+                    tile_stack.sprite_stack.insert(0, sprite);
+                    *floor_sprite = Some(sprite);
+
+                    let repair_sprite = entities
+                        .build_entity()
+                        .with(SpriteRender {
+                            sprite_sheet: sprite_sheet.clone(),
+                            sprite_number: spriteIds::REPAIRGRIDSQUARE,
+                        }, &mut sprites)
+                        .with(tile_sprite_transform(tile_position, HIGHLIGHT_LAYER), &mut transforms)
+                        .build();
+                    tile_stack.sprite_stack.push(repair_sprite);
+                    self.repair_sprites.push((tile, repair_sprite, REPAIR_FEEDBACK_SECONDS));
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Turn flow of a battle
+//!
+//! Once the field is set up, the `Spybotics` state pushes the `UploadState`. Starting the battle
+//! switches to the `PlayerTurnState`, and from there on the battle alternates between
+//! `PlayerTurnState` and `EnemyTurnState` until a victory or defeat condition of the level is met,
+//! which switches to the `VictoryState` or the `DefeatState` showing the result.
+//!
+//! A simulated battle runs through the same states: the roster is uploaded right away, the AI
+//! plays both sides and the result returns to the `Spybotics` state without being shown.
+
+use amethyst::{
+    ecs::{Entity, Join, World, WorldExt},
+    input::{is_close_requested, is_key_down},
+    prelude::*,
+    winit::VirtualKeyCode,
+};
+
+use log::{error, info};
+
+use crate::action::ActionQueue;
+use crate::ai::{Difficulty, TurnPlanner};
+use crate::board::Board;
+use crate::body::{ProgramBody, Side};
+use crate::command::CommandReport;
+use crate::history::{redo, undo, TurnHistory};
+use crate::objective::{BattleOutcome, BattleResult};
+use crate::pickup::BattleStats;
+use crate::replay::{record_uploads, Replay};
+use crate::savegame::save_battle;
+use crate::selection::Selection;
+use crate::text::{create_label, delete_labels};
+use crate::upload::{clear_upload_zones, upload_roster, Roster, UploadSelection};
+
+/// Turns after which a simulated battle without a result is called off
+const MAX_TURNS: u32 = 200;
+
+/// Keys choosing the roster entry to upload or the command of the selected program
+pub const NUMBER_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// The phases of a battle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The field is not set up yet
+    Setup,
+    Upload,
+    PlayerTurn,
+    EnemyTurn,
+    Victory,
+    Defeat,
+}
+
+impl Default for Phase {
+    fn default() -> Self {
+        Phase::Setup
+    }
+}
+
+/// Resource describing the current state of the turn flow, so systems can check whose turn it is
+#[derive(Debug, Default)]
+pub struct TurnState {
+    pub phase: Phase,
+    /// Number of the current turn, starting with 1 for the first player turn
+    pub turn: u32,
+    /// Whether a replay is played back, so the player only watches
+    pub replaying: bool,
+    /// Whether the AI plays both sides without a window, see the `simulate` binary
+    pub simulated: bool,
+}
+
+/// Restores the moves of all programs of the given side
+pub fn begin_turn(world: &mut World, side: Side) {
+    let mut bodies = world.write_storage::<ProgramBody>();
+    for body in (&mut bodies).join().filter(|body| body.side == side) {
+        body.begin_turn();
+    }
+}
+
+/// Ends the turn of all programs of the given side
+pub fn end_turn(world: &mut World, side: Side) {
+    let mut bodies = world.write_storage::<ProgramBody>();
+    for body in (&mut bodies).join().filter(|body| body.side == side) {
+        body.end_turn();
+    }
+}
+
+/// Whether every program of the given side has finished its turn
+fn side_done(world: &World, side: Side) -> bool {
+    let bodies = world.read_storage::<ProgramBody>();
+    (&bodies).join()
+        .filter(|body| body.side == side)
+        .all(|body| body.is_done())
+}
+
+/// Returns the transition to the result screen, once a condition of the level is met
+fn battle_outcome(world: &World) -> Option<SimpleTrans> {
+    match world.read_resource::<BattleOutcome>().result {
+        Some(BattleResult::Victory) => Some(Trans::Switch(Box::new(VictoryState::default()))),
+        Some(BattleResult::Defeat) => Some(Trans::Switch(Box::new(DefeatState::default()))),
+        None => None,
+    }
+}
+
+/// Shows the result of the last executed command below the field, replacing the previous report
+fn report_command(world: &mut World, labels: &mut Vec<Entity>) {
+    let text = match world.write_resource::<CommandReport>().last.take() {
+        Some(Ok(outcome)) => outcome.to_string(),
+        Some(Err(error)) => format!("Command failed: {}", error),
+        None => return,
+    };
+    // Without a window the `ActionSystem` logging the command is all there is
+    if world.read_resource::<TurnState>().simulated {
+        return;
+    }
+    delete_labels(world, labels);
+    labels.push(create_label(world, "command_report", text, -270.0, 18.0));
+}
+
+fn set_phase(world: &mut World, phase: Phase) {
+    world.write_resource::<TurnState>().phase = phase;
+}
+
+/// Starts the battle once the player uploaded at least one program
+fn start_battle(world: &mut World) -> SimpleTrans {
+    let uploaded = (&world.read_storage::<ProgramBody>())
+        .join()
+        .any(|body| body.side == Side::Player);
+    if !uploaded {
+        info!("Upload at least one program to start the battle");
+        return Trans::None;
+    }
+    info!("Starting the battle");
+    clear_upload_zones(world);
+    record_uploads(world);
+    Trans::Switch(Box::new(PlayerTurnState::default()))
+}
+
+/// Placing programs on the field before the battle starts. A simulated battle uploads the roster
+/// and starts right away.
+pub struct UploadState;
+
+impl SimpleState for UploadState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        set_phase(data.world, Phase::Upload);
+        info!("Choose a program with the number keys and click an upload zone, press enter to start the battle");
+        info!("Roster: {}", data.world.read_resource::<Roster>().describe());
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if let Some(entry) = NUMBER_KEYS.iter().position(|key| is_key_down(&event, *key)) {
+                if let Some(roster_entry) = data.world.read_resource::<Roster>().entries.get(entry) {
+                    info!("Uploading {}", roster_entry.program);
+                }
+                data.world.write_resource::<UploadSelection>().entry = entry;
+            }
+            if is_key_down(&event, VirtualKeyCode::Return) {
+                return start_battle(data.world);
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if !data.world.read_resource::<TurnState>().simulated {
+            return Trans::None;
+        }
+        upload_roster(data.world);
+        match start_battle(data.world) {
+            Trans::None => {
+                error!("No program could be uploaded, the simulation ends");
+                Trans::Quit
+            }
+            started => started,
+        }
+    }
+}
+
+/// The player moves their programs and executes commands. Z undoes and Y redoes the actions of
+/// the turn, F5 saves the battle. In a simulated battle the AI plays the player programs.
+#[derive(Default)]
+pub struct PlayerTurnState {
+    /// Plays the turn in a simulated battle
+    ai: Option<AiTurn>,
+    /// Label reporting the last executed command
+    report: Vec<Entity>,
+}
+
+impl SimpleState for PlayerTurnState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if data.world.read_resource::<TurnState>().simulated {
+            self.ai = Some(AiTurn::new(data.world, Side::Player));
+        }
+        data.world.write_resource::<TurnHistory>().clear();
+        // A resumed battle continues the saved turn
+        if data.world.read_resource::<TurnState>().phase == Phase::PlayerTurn {
+            info!("Turn {}: player, resumed", data.world.read_resource::<TurnState>().turn);
+            return;
+        }
+        let turn = {
+            let mut turn_state = data.world.write_resource::<TurnState>();
+            turn_state.phase = Phase::PlayerTurn;
+            turn_state.turn += 1;
+            turn_state.turn
+        };
+        info!("Turn {}: player", turn);
+        begin_turn(data.world, Side::Player);
+        data.world.write_resource::<Replay>().begin_turn(Side::Player);
+        save_battle(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.report);
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Return) {
+                end_turn(data.world, Side::Player);
+            }
+            if let Some(command) = NUMBER_KEYS.iter().position(|key| is_key_down(&event, *key)) {
+                data.world.write_resource::<Selection>().command = Some(command);
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                data.world.write_resource::<Selection>().command = None;
+            }
+            if is_key_down(&event, VirtualKeyCode::Z) {
+                undo(data.world);
+            }
+            if is_key_down(&event, VirtualKeyCode::Y) {
+                redo(data.world);
+            }
+            if is_key_down(&event, VirtualKeyCode::F5) {
+                save_battle(data.world);
+            }
+            if is_key_down(&event, VirtualKeyCode::Space) {
+                // the selected program passes for the rest of the turn
+                let selected = data.world.read_resource::<Selection>().program;
+                if let Some(selected) = selected {
+                    if let Some(body) = data.world.write_storage::<ProgramBody>().get_mut(selected) {
+                        body.end_turn();
+                    }
+                }
+            }
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        report_command(data.world, &mut self.report);
+        if let Some(outcome) = battle_outcome(data.world) {
+            return outcome;
+        }
+        if let Some(ai) = &mut self.ai {
+            if data.world.read_resource::<TurnState>().turn > MAX_TURNS {
+                info!("No result after {} turns, the simulation ends", MAX_TURNS);
+                return Trans::Pop;
+            }
+            if ai.play(data.world) {
+                return Trans::Switch(Box::new(EnemyTurnState::default()));
+            }
+            return Trans::None;
+        }
+        if side_done(data.world, Side::Player) {
+            return Trans::Switch(Box::new(EnemyTurnState::default()));
+        }
+        Trans::None
+    }
+}
+
+/// Plays the turn of one side with the AI, one program after the other.
+///
+/// The turn of the next program is planned in the background while the state keeps updating.
+struct AiTurn {
+    side: Side,
+    /// The program whose planned actions are being applied
+    acting: Option<Entity>,
+    planner: TurnPlanner,
+}
+
+impl AiTurn {
+
+    fn new(world: &World, side: Side) -> Self {
+        let difficulty = *world.read_resource::<Difficulty>();
+        // A simulated battle plays the same on every machine
+        let planner = if world.read_resource::<TurnState>().simulated {
+            TurnPlanner::deterministic(difficulty)
+        } else {
+            TurnPlanner::new(difficulty)
+        };
+        AiTurn {
+            side,
+            acting: None,
+            planner,
+        }
+    }
+
+    /// Queues the planned actions of the next program, returns whether all programs of the side
+    /// have finished their turn
+    fn play(&mut self, world: &mut World) -> bool {
+        if !world.read_resource::<ActionQueue>().is_empty() {
+            return false;
+        }
+        if self.planner.is_planning() {
+            if let Some((actor, actions)) = self.planner.poll() {
+                let mut action_queue = world.write_resource::<ActionQueue>();
+                for action in actions {
+                    action_queue.push(action);
+                }
+                self.acting = Some(actor);
+            }
+            return false;
+        }
+
+        // The actions of the acting program are applied, whatever is left of its turn is skipped
+        if let Some(acting) = self.acting.take() {
+            if let Some(body) = world.write_storage::<ProgramBody>().get_mut(acting) {
+                body.end_turn();
+            }
+        }
+
+        let waiting: Vec<Entity> = {
+            let entities = world.entities();
+            let bodies = world.read_storage::<ProgramBody>();
+            (&entities, &bodies)
+                .join()
+                .filter(|(_, body)| body.side == self.side && !body.is_done())
+                .map(|(entity, _)| entity)
+                .collect()
+        };
+        match waiting.first() {
+            Some(next) => {
+                self.planner.start(Board::from_world(world), *next, waiting.len());
+                false
+            }
+            None => true,
+        }
+    }
+}
+
+/// The enemy programs move and execute commands, one program after the other
+#[derive(Default)]
+pub struct EnemyTurnState {
+    ai: Option<AiTurn>,
+    /// Label reporting the last executed command
+    report: Vec<Entity>,
+}
+
+impl SimpleState for EnemyTurnState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if data.world.read_resource::<TurnState>().phase == Phase::EnemyTurn {
+            info!("Turn {}: enemy, resumed", data.world.read_resource::<TurnState>().turn);
+            return;
+        }
+        set_phase(data.world, Phase::EnemyTurn);
+        info!("Turn {}: enemy", data.world.read_resource::<TurnState>().turn);
+        begin_turn(data.world, Side::Enemy);
+        data.world.write_resource::<Replay>().begin_turn(Side::Enemy);
+        save_battle(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.report);
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        report_command(data.world, &mut self.report);
+        if let Some(outcome) = battle_outcome(data.world) {
+            return outcome;
+        }
+        let world = &mut *data.world;
+        let ai = self.ai.get_or_insert_with(|| AiTurn::new(world, Side::Enemy));
+        if ai.play(world) {
+            return Trans::Switch(Box::new(PlayerTurnState::default()));
+        }
+        Trans::None
+    }
+}
+
+/// Shows the result of the battle and what the player collected
+fn show_result(world: &mut World, title: &str) -> Vec<Entity> {
+    let summary = {
+        let battle_stats = world.read_resource::<BattleStats>();
+        format!(
+            "{} credits and {} data items collected in {} turns",
+            battle_stats.credits_collected,
+            battle_stats.data_items_collected,
+            world.read_resource::<TurnState>().turn,
+        )
+    };
+    info!("{}! {}", title, summary);
+    if world.read_resource::<TurnState>().simulated {
+        return Vec::new();
+    }
+    vec![
+        create_label(world, "result_title", title.to_string(), 60.0, 48.0),
+        create_label(world, "result_summary", summary, 0.0, 24.0),
+        create_label(world, "result_hint", "Press enter to continue".to_string(), -60.0, 18.0),
+    ]
+}
+
+/// Returns to the `Spybotics` state right away in a simulated battle, nobody acknowledges the result
+fn end_simulation(world: &World) -> SimpleTrans {
+    if world.read_resource::<TurnState>().simulated {
+        Trans::Pop
+    } else {
+        Trans::None
+    }
+}
+
+/// Returns to the `Spybotics` state when the result has been acknowledged
+fn acknowledge_result(event: StateEvent) -> SimpleTrans {
+    if let StateEvent::Window(event) = &event {
+        if is_close_requested(&event) {
+            return Trans::Quit;
+        }
+        if is_key_down(&event, VirtualKeyCode::Return) || is_key_down(&event, VirtualKeyCode::Escape) {
+            return Trans::Pop;
+        }
+    }
+    Trans::None
+}
+
+/// A victory condition of the level has been met
+#[derive(Default)]
+pub struct VictoryState {
+    labels: Vec<Entity>,
+}
+
+impl SimpleState for VictoryState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        set_phase(data.world, Phase::Victory);
+        self.labels = show_result(data.world, "Victory");
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        acknowledge_result(event)
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        end_simulation(data.world)
+    }
+}
+
+/// A defeat condition of the level has been met
+#[derive(Default)]
+pub struct DefeatState {
+    labels: Vec<Entity>,
+}
+
+impl SimpleState for DefeatState {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        set_phase(data.world, Phase::Defeat);
+        self.labels = show_result(data.world, "Defeat");
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_labels(data.world, &mut self.labels);
+    }
+
+    fn handle_event(&mut self, _data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        acknowledge_result(event)
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        end_simulation(data.world)
+    }
+}
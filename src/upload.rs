@@ -0,0 +1,235 @@
+//! Uploading programs onto the upload zones before the battle
+
+use amethyst::{
+    ecs::{Entities, Entity, Join, Read, ReadStorage, System, World, WorldExt, Write, WriteStorage},
+    renderer::SpriteRender,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::body::{place_programs, ProgramBody, Side};
+use crate::grid::GameField;
+use crate::level::{Level, UploadZone};
+use crate::program::ProgramCatalog;
+use crate::tile::FLOOR_SPRITE;
+use crate::turn::{Phase, TurnState};
+use crate::{GameTileSpriteStack, TileClick};
+
+/// Copies of a program available for upload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RosterEntry {
+    pub program: String,
+    pub copies: u32,
+}
+
+/// Resource listing the programs the player can upload
+#[derive(Debug, Clone, Default)]
+pub struct Roster {
+    pub entries: Vec<RosterEntry>,
+}
+
+impl Roster {
+
+    pub fn describe(&self) -> String {
+        let entries: Vec<String> = self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| format!("[{}] {} x{}", index + 1, entry.program, entry.copies))
+            .collect();
+        entries.join(", ")
+    }
+
+    fn give_back(&mut self, program: &str) {
+        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.program == program) {
+            entry.copies += 1;
+        }
+    }
+}
+
+/// Resource listing the programs the player owns. Every battle starts with a roster of all of them.
+#[derive(Debug, Clone)]
+pub struct OwnedPrograms {
+    pub programs: Vec<RosterEntry>,
+}
+
+impl Default for OwnedPrograms {
+    /// The programs every player starts with
+    fn default() -> Self {
+        let entry = |program: &str, copies| RosterEntry {
+            program: program.to_string(),
+            copies,
+        };
+        OwnedPrograms {
+            programs: vec![
+                entry("HACK", 2),
+                entry("BIT_MAN", 1),
+                entry("SLINGSHOT", 1),
+            ],
+        }
+    }
+}
+
+impl OwnedPrograms {
+
+    pub fn copies(&self, program: &str) -> u32 {
+        self.programs
+            .iter()
+            .find(|entry| entry.program == program)
+            .map_or(0, |entry| entry.copies)
+    }
+
+    pub fn add(&mut self, program: &str) {
+        match self.programs.iter_mut().find(|entry| entry.program == program) {
+            Some(entry) => entry.copies += 1,
+            None => self.programs.push(RosterEntry {
+                program: program.to_string(),
+                copies: 1,
+            }),
+        }
+    }
+
+    /// The roster to upload from at the start of a battle
+    pub fn roster(&self) -> Roster {
+        Roster {
+            entries: self.programs.clone(),
+        }
+    }
+}
+
+/// Resource holding the roster entry the player uploads next
+#[derive(Debug, Default)]
+pub struct UploadSelection {
+    pub entry: usize,
+}
+
+/// Uploads the chosen program onto a clicked player upload zone, or takes an uploaded program back
+#[derive(Default)]
+pub struct UploadSystem;
+
+impl<'a> System<'a> for UploadSystem {
+
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, TurnState>,
+        Read<'a, ProgramCatalog>,
+        Read<'a, GameField>,
+        Read<'a, UploadSelection>,
+        Write<'a, TileClick>,
+        Write<'a, Roster>,
+        ReadStorage<'a, UploadZone>,
+        WriteStorage<'a, ProgramBody>,
+    );
+
+    fn run(&mut self, ( entities,
+                        turn_state,
+                        catalog,
+                        game_field,
+                        upload_selection,
+                        mut tile_click,
+                        mut roster,
+                        upload_zones,
+                        mut bodies,
+                        ): Self::SystemData) {
+
+        if turn_state.phase != Phase::Upload {
+            return;
+        }
+        let grid_position = match tile_click.grid_position.take() {
+            Some(grid_position) => grid_position,
+            None => return,
+        };
+        let is_player_zone = game_field
+            .tile(grid_position)
+            .and_then(|tile| upload_zones.get(tile))
+            .map_or(false, |zone| zone.side == Side::Player);
+        if !is_player_zone {
+            return;
+        }
+
+        let uploaded = (&entities, &bodies)
+            .join()
+            .find(|(_, body)| body.occupies(grid_position))
+            .map(|(entity, body)| (entity, body.program.name.clone()));
+        if let Some((entity, program)) = uploaded {
+            bodies.remove(entity);
+            entities.delete(entity).expect("Failed to delete uploaded program.");
+            roster.give_back(&program);
+            info!("Took back {}, roster: {}", program, roster.describe());
+            return;
+        }
+
+        let entry = match roster.entries.get_mut(upload_selection.entry) {
+            Some(entry) if entry.copies > 0 => entry,
+            _ => {
+                info!("No copies left of the chosen program, roster: {}", roster.describe());
+                return;
+            }
+        };
+        let program = match catalog.get(&entry.program) {
+            Some(program) => program,
+            None => {
+                warn!("Program {} is missing in the program catalog", entry.program);
+                return;
+            }
+        };
+
+        entry.copies -= 1;
+        entities
+            .build_entity()
+            .with(ProgramBody::new(program.clone(), Side::Player, grid_position), &mut bodies)
+            .build();
+        info!("Uploaded {}, roster: {}", program.name, roster.describe());
+    }
+}
+
+/// Uploads the roster onto the player upload zones in the order of its entries, for a battle the
+/// AI plays on both sides
+pub fn upload_roster(world: &mut World) {
+    let zones: Vec<(u32, u32)> = world.read_resource::<Level>().upload_zones
+        .iter()
+        .filter(|(_, zone)| zone.side == Side::Player)
+        .map(|(grid_position, _)| *grid_position)
+        .collect();
+    let mut zones = zones.into_iter();
+
+    let mut uploads = Vec::new();
+    for entry in world.write_resource::<Roster>().entries.iter_mut() {
+        while entry.copies > 0 {
+            let grid_position = match zones.next() {
+                Some(grid_position) => grid_position,
+                None => break,
+            };
+            entry.copies -= 1;
+            uploads.push((entry.program.clone(), grid_position));
+        }
+    }
+    for (program, grid_position) in uploads.iter() {
+        info!("Uploaded {} at {:?}", program, grid_position);
+    }
+    place_programs(world, &uploads, Side::Player);
+}
+
+/// Turns all upload zones into normal tiles when the battle starts
+pub fn clear_upload_zones(world: &mut World) {
+    let zones: Vec<Entity> = {
+        let entities = world.entities();
+        let upload_zones = world.read_storage::<UploadZone>();
+        (&entities, &upload_zones).join().map(|(tile, _)| tile).collect()
+    };
+
+    let mut upload_zones = world.write_storage::<UploadZone>();
+    let game_tile_sprite_stack = world.read_storage::<GameTileSpriteStack>();
+    let mut sprites = world.write_storage::<SpriteRender>();
+    for tile in zones {
+        upload_zones.remove(tile);
+        // the floor sprite is at the bottom of the stack
+        let floor_sprite = game_tile_sprite_stack
+            .get(tile)
+            .and_then(|tile_stack| tile_stack.sprite_stack.first())
+            .and_then(|sprite| sprites.get_mut(*sprite));
+        if let Some(floor_sprite) = floor_sprite {
+            floor_sprite.sprite_number = FLOOR_SPRITE;
+        }
+    }
+}